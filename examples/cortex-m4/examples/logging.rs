@@ -41,9 +41,12 @@ fn main() -> ! {
     */
     spawn!(task1, stack1, {
         hprintln!("TASK 1");
-        logging::process(|log: logging::LogEvent| {
-            hprintln!("{:?}", log);
-        });
+        if let Some(token) = logging::claim() {
+            logging::process(&token, |log: logging::LogEvent| {
+                hprintln!("{:?}", log);
+            });
+            logging::release_claim(token);
+        }
     });
     spawn!(task2, stack2, {
         hprintln!("TASK 2");
@@ -1,5 +1,7 @@
 //! Kernel configuration.  `Private`
 
+use crate::system::pi_stack::EmptyPopAction;
+
 #[cfg(all(
     any(feature = "tasks_32", feature = "default"),
     not(any(feature = "tasks_16", feature = "tasks_8"))
@@ -24,6 +26,23 @@ pub const MAX_RESOURCES: usize = 16;
 #[cfg(all(feature = "resources_64", not(feature = "resources_32")))]
 pub const MAX_RESOURCES: usize = 64;
 
+/// The depth of the pi_stack, i.e. the maximum nesting of resource locks. Defaults to `MAX_RESOURCES`.
+#[cfg(not(any(
+    feature = "lock_nesting_4",
+    feature = "lock_nesting_8",
+    feature = "lock_nesting_16"
+)))]
+pub const MAX_LOCK_NESTING: usize = MAX_RESOURCES;
+
+#[cfg(feature = "lock_nesting_4")]
+pub const MAX_LOCK_NESTING: usize = 4;
+
+#[cfg(feature = "lock_nesting_8")]
+pub const MAX_LOCK_NESTING: usize = 8;
+
+#[cfg(feature = "lock_nesting_16")]
+pub const MAX_LOCK_NESTING: usize = 16;
+
 #[cfg(feature = "events_32")]
 pub const EVENT_COUNT: usize = 32;
 
@@ -34,3 +53,45 @@ pub const EVENT_COUNT: usize = 16;
 pub const EVENT_COUNT: usize = 64;
 
 pub const MAX_LOGS: usize = 128;
+
+/// The action taken when a resource that was never locked is unlocked.
+pub const EMPTY_POP_ACTION: EmptyPopAction = EmptyPopAction::ReturnError;
+
+/// The maximum number of tasks which can be preempted at once. Every preempted task holds an
+/// exception frame on its stack, once the limit is reached further preemptions are deferred until a
+/// preempted task resumes. Set it to `MAX_TASKS` to never defer a preemption.
+pub const MAX_PREEMPT_NESTING: usize = 4;
+
+/// The number of ticks a task of a round-robin band runs before the next task of the band gets its
+/// turn.
+#[cfg(feature = "round_robin")]
+pub const RR_QUANTUM: u32 = 10;
+
+/// The number of messages a message queue holds for each receiver.
+pub const MAX_MSG_DEPTH: usize = 4;
+
+/// The maximum number of outstanding requests of a request/reply object.
+pub const MAX_PENDING_REQUESTS: usize = 8;
+
+/// Number of words every task stack must reserve for nested interrupts. On Cortex-M, interrupts
+/// stack their exception frames onto the stack of the task they preempt, hence a task stack must
+/// budget for the deepest interrupt nesting on top of the task's own usage. Defaults to 32 words,
+/// the `isr_stack_16` feature lowers the reserve to 16 words and `isr_stack_0` disables it.
+#[cfg(not(any(feature = "isr_stack_0", feature = "isr_stack_16")))]
+pub const RESERVED_ISR_STACK: usize = 32;
+
+#[cfg(all(feature = "isr_stack_16", not(feature = "isr_stack_0")))]
+pub const RESERVED_ISR_STACK: usize = 16;
+
+#[cfg(feature = "isr_stack_0")]
+pub const RESERVED_ISR_STACK: usize = 0;
+
+/// The BASEPRI value written by kernel critical sections with the `basepri` feature. Interrupts with a
+/// priority value lower than this (i.e. higher priority) are never masked by the kernel. The kernel
+/// sets SysTick and SVCall to this priority value, and PendSV to the lowest priority, on `init`.
+#[cfg(feature = "basepri")]
+pub const KERNEL_BASEPRI: u8 = 0x40;
+
+/// The value written to the lowest word of every task stack, which is checked on each context switch.
+#[cfg(feature = "stack_check")]
+pub const STACK_CANARY: u32 = 0xDEAD_BEEF;
@@ -0,0 +1,77 @@
+//! Kernel timer based implementation of the `embedded-hal` delay traits.
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+
+use crate::kernel::tasks::sleep;
+use crate::kernel::timer::get_time;
+use crate::system::scheduler::Scheduler;
+use crate::utils::arch::wait_for_interrupt;
+use crate::utils::kernel_cell::KernelCell;
+
+/// A delay provider for `embedded-hal` drivers backed by the kernel timer. The calling task sleeps
+/// for the delay, hence every other task, including those of lower priority, runs meanwhile.
+pub struct KernelDelay {
+    task_manager: &'static KernelCell<Scheduler>,
+    /// Duration of a single kernel tick in microseconds.
+    tick_us: u32,
+}
+
+impl KernelDelay {
+    /// Creates a delay provider for the tasks of `task_manager`, `tick_us` is the duration of a
+    /// kernel tick in microseconds and must not be zero.
+    pub const fn new(task_manager: &'static KernelCell<Scheduler>, tick_us: u32) -> Self {
+        Self {
+            task_manager,
+            tick_us,
+        }
+    }
+
+    /// Waits for at least `us` microseconds, the delay is rounded up to whole kernel ticks. The idle
+    /// task cannot sleep, it waits for the ticks with the CPU asleep instead.
+    fn delay(&self, us: u64) {
+        let tick_us = self.tick_us as u64;
+        // the first tick can be partial, so wait for one more tick boundary.
+        let ticks = ((us + tick_us - 1) / tick_us) as u32 + 1;
+        if sleep(self.task_manager, ticks).is_err() {
+            let start = get_time();
+            while get_time().wrapping_sub(start) < ticks {
+                wait_for_interrupt();
+            }
+        }
+    }
+}
+
+impl DelayUs<u32> for KernelDelay {
+    fn delay_us(&mut self, us: u32) {
+        self.delay(us as u64);
+    }
+}
+
+impl DelayUs<u16> for KernelDelay {
+    fn delay_us(&mut self, us: u16) {
+        self.delay(us as u64);
+    }
+}
+
+impl DelayUs<u8> for KernelDelay {
+    fn delay_us(&mut self, us: u8) {
+        self.delay(us as u64);
+    }
+}
+
+impl DelayMs<u32> for KernelDelay {
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay(ms as u64 * 1000);
+    }
+}
+
+impl DelayMs<u16> for KernelDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        self.delay(ms as u64 * 1000);
+    }
+}
+
+impl DelayMs<u8> for KernelDelay {
+    fn delay_ms(&mut self, ms: u8) {
+        self.delay(ms as u64 * 1000);
+    }
+}
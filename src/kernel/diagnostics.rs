@@ -0,0 +1,58 @@
+//! # Diagnostics
+//! Exports the kernel state as a fixed layout binary frame, for host side monitoring tools.
+
+use crate::system::pi_stack::PiStack;
+use crate::system::scheduler::Scheduler;
+use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
+
+#[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
+use crate::kernel::events;
+
+/// Version of the frame layout, the first byte of every frame.
+pub const FRAME_VERSION: u8 = 1;
+
+/// Size of a frame in bytes.
+pub const FRAME_LEN: usize = 18;
+
+/// Encodes the kernel state into `buf` and returns the number of bytes written, 0 if `buf` is
+/// shorter than `FRAME_LEN`. Multi-byte fields are little endian. The frame layout is:
+///
+/// | Offset | Size | Field |
+/// |--------|------|-------|
+/// | 0  | 1 | `FRAME_VERSION` |
+/// | 1  | 1 | TaskId of the currently running task |
+/// | 2  | 4 | `active_tasks` |
+/// | 6  | 4 | `blocked_tasks` |
+/// | 10 | 4 | system ceiling (-1 if no resource is locked) |
+/// | 14 | 2 | pi_stack depth |
+/// | 16 | 2 | events pending acknowledgment (0 without events) |
+pub fn encode_state(
+    task_manager: &'static KernelCell<Scheduler>,
+    pi_stack: &'static KernelCell<PiStack>,
+    buf: &mut [u8],
+) -> usize {
+    if buf.len() < FRAME_LEN {
+        return 0;
+    }
+    #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
+    let pending_events = events::pending_count() as u16;
+    #[cfg(not(any(feature = "events_32", feature = "events_16", feature = "events_64")))]
+    let pending_events: u16 = 0;
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = task_manager.borrow(cs_token).borrow();
+        buf[0] = FRAME_VERSION;
+        buf[1] = handler.curr_tid as u8;
+        buf[2..6].copy_from_slice(&handler.active_tasks.to_le_bytes());
+        buf[6..10].copy_from_slice(&handler.blocked_tasks.to_le_bytes());
+        drop(handler);
+        spinunlock(&TASKMANAGER_LOCK);
+        let pi_stack = pi_stack.borrow(cs_token).borrow();
+        buf[10..14].copy_from_slice(&pi_stack.system_ceiling.to_le_bytes());
+        buf[14..16].copy_from_slice(&(pi_stack.depth() as u16).to_le_bytes());
+    });
+    buf[16..18].copy_from_slice(&pending_events.to_le_bytes());
+    FRAME_LEN
+}
@@ -2,18 +2,19 @@
 //!
 //! Defines Kernel routines for Event Management.
 
-use core::cell::RefCell;
-
-use crate::utils::arch::{critical_section, Mutex};
-
+use crate::config::MAX_TASKS;
+use crate::kernel::tasks::{release, schedule, validate_tasks};
 use crate::kernel::timer::get_time;
 use crate::priv_execute;
 use crate::system::event::*;
+use crate::system::scheduler::{Scheduler, TaskId};
+use crate::utils::arch::critical_section;
 use crate::utils::arch::is_privileged;
+use crate::utils::kernel_cell::KernelCell;
 use crate::KernelError;
 
 /// Global Instance of EventManager
-static event_manager: Mutex<RefCell<EventTable>> = Mutex::new(RefCell::new(EventTable::new()));
+static event_manager: KernelCell<EventTable> = KernelCell::new(EventTable::new());
 
 /// Sweeps all events in event table and updates their counter, if counter has expired
 /// then it dispatches the event and resets the counter.
@@ -51,3 +52,48 @@ pub fn new(is_enabled: bool, threshold: u32, handler: fn() -> ()) -> Result<Even
         })
     })
 }
+
+/// Binds the task `tid` to the event, so that raising the event from an ISR releases the task.
+/// The task must have been created.
+pub fn bind_isr(
+    event_id: EventId,
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<(), KernelError> {
+    if tid as usize >= MAX_TASKS {
+        return Err(KernelError::LimitExceeded);
+    }
+    validate_tasks(task_manager, 1 << tid)?;
+    critical_section(|cs_token| {
+        event_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .bind(event_id, task_manager, tid)
+    })
+}
+
+/// Raises the event from an ISR and releases the bound task. If the task has not acknowledged the
+/// previous raise, the raise is coalesced and the task is not released again.
+/// Returns true if the task was released.
+pub fn raise(event_id: EventId) -> Result<bool, KernelError> {
+    let bound_task =
+        critical_section(|cs_token| event_manager.borrow(cs_token).borrow_mut().raise(event_id))?;
+    match bound_task {
+        Some((task_manager, tid)) => {
+            release(task_manager, 1 << tid);
+            schedule(task_manager);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Acknowledges the event, called by the bound task once it has handled the event.
+pub fn ack(event_id: EventId) -> Result<(), KernelError> {
+    critical_section(|cs_token| event_manager.borrow(cs_token).borrow_mut().ack(event_id))
+}
+
+/// Returns the number of events raised from an ISR which the bound task has not acknowledged yet.
+pub fn pending_count() -> usize {
+    critical_section(|cs_token| event_manager.borrow(cs_token).borrow().pending_count())
+}
@@ -1,14 +1,16 @@
-use core::cell::RefCell;
-
 use crate::kernel::timer::get_time;
 use crate::priv_execute;
 use crate::system::scheduler::*;
 use crate::system::system_logger::*;
 use crate::utils::arch::is_privileged;
-use crate::utils::arch::{critical_section, svc_call, Mutex};
+use crate::utils::arch::{critical_section, svc_call};
+use crate::utils::kernel_cell::KernelCell;
 use crate::KernelError;
 
-static Logger: Mutex<RefCell<SystemLogger>> = Mutex::new(RefCell::new(SystemLogger::new()));
+static Logger: KernelCell<SystemLogger> = KernelCell::new(SystemLogger::new());
+
+/// Log sink drained by the idle task and the maximum number of events it drains per iteration.
+static IDLE_SINK: KernelCell<Option<(fn(LogEvent), usize)>> = KernelCell::new(None);
 
 pub fn report(event_type: LogEventType) {
     critical_section(|cs_token| {
@@ -19,7 +21,34 @@ pub fn report(event_type: LogEventType) {
     })
 }
 
-pub fn process<F>(handler: F)
+/// Grants the exclusive right to drain the log. Only one `LogToken` exists at a time.
+pub struct LogToken {
+    _private: (),
+}
+
+/// True while a `LogToken` is held.
+static CLAIMED: KernelCell<bool> = KernelCell::new(false);
+
+/// Claims the right to drain the log, returns `None` if it is already claimed.
+pub fn claim() -> Option<LogToken> {
+    critical_section(|cs_token| {
+        let claimed = &mut *CLAIMED.borrow(cs_token).borrow_mut();
+        if *claimed {
+            return None;
+        }
+        *claimed = true;
+        Some(LogToken { _private: () })
+    })
+}
+
+/// Gives up the right to drain the log, so that it can be claimed again.
+pub fn release_claim(_token: LogToken) {
+    critical_section(|cs_token| {
+        *CLAIMED.borrow(cs_token).borrow_mut() = false;
+    })
+}
+
+pub fn process<F>(_token: &LogToken, handler: F)
 where
     F: Fn(LogEvent),
 {
@@ -30,6 +59,58 @@ where
     })
 }
 
+/// Processes at most `count` log events and returns the number of events processed.
+pub fn process_n<F>(_token: &LogToken, count: usize, handler: F) -> usize
+where
+    F: Fn(LogEvent),
+{
+    drain_n(count, handler)
+}
+
+fn drain_n<F>(count: usize, handler: F) -> usize
+where
+    F: Fn(LogEvent),
+{
+    critical_section(|cs_token| {
+        let mut processed = 0;
+        while processed < count {
+            let event = Logger.borrow(cs_token).borrow_mut().pop();
+            match event {
+                Some(event) => handler(event),
+                None => break,
+            }
+            processed += 1;
+        }
+        processed
+    })
+}
+
+/// Sets a log sink that is drained by the idle task, at most `count` events are
+/// processed each time the idle task runs.
+pub fn set_idle_sink(handler: fn(LogEvent), count: usize) {
+    critical_section(|cs_token| {
+        *IDLE_SINK.borrow(cs_token).borrow_mut() = Some((handler, count));
+    })
+}
+
+/// Drains the log into the idle sink, if one is set and the log is not claimed by a task.
+pub fn process_idle_sink() {
+    let sink = critical_section(|cs_token| {
+        if *CLAIMED.borrow(cs_token).borrow() {
+            return None;
+        }
+        *IDLE_SINK.borrow(cs_token).borrow()
+    });
+    if let Some((handler, count)) = sink {
+        drain_n(count, handler);
+    }
+}
+
+/// Discards all the buffered log events without processing them.
+pub fn clear() {
+    critical_section(|cs_token| Logger.borrow(cs_token).borrow_mut().clear())
+}
+
 pub fn set_all(val: bool) {
     critical_section(|cs_token| {
         Logger.borrow(cs_token).borrow_mut().release_log = val;
@@ -42,6 +123,7 @@ pub fn set_all(val: bool) {
         Logger.borrow(cs_token).borrow_mut().message_recieve_log = val;
         Logger.borrow(cs_token).borrow_mut().semaphore_signal_log = val;
         Logger.borrow(cs_token).borrow_mut().semaphore_reset_log = val;
+        Logger.borrow(cs_token).borrow_mut().priority_inheritance_log = val;
         Logger.borrow(cs_token).borrow_mut().timer_event_log = val;
     })
 }
@@ -106,6 +188,12 @@ pub fn set_semaphore_reset(val: bool) {
     })
 }
 
+pub fn set_priority_inheritance(val: bool) {
+    critical_section(|cs_token| {
+        Logger.borrow(cs_token).borrow_mut().priority_inheritance_log = val;
+    })
+}
+
 pub fn set_timer_event(val: bool) {
     critical_section(|cs_token| {
         Logger.borrow(cs_token).borrow_mut().timer_event_log = val;
@@ -152,6 +240,10 @@ pub fn get_semaphore_reset() -> bool {
     critical_section(|cs_token| Logger.borrow(cs_token).borrow_mut().semaphore_reset_log)
 }
 
+pub fn get_priority_inheritance() -> bool {
+    critical_section(|cs_token| Logger.borrow(cs_token).borrow_mut().priority_inheritance_log)
+}
+
 pub fn get_timer_event() -> bool {
     critical_section(|cs_token| Logger.borrow(cs_token).borrow_mut().timer_event_log)
 }
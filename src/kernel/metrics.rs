@@ -0,0 +1,73 @@
+//! # Metrics Module
+//!
+//! Defines Kernel routines which collect and report runtime metrics.
+
+use crate::config::MAX_TASKS;
+use crate::system::metrics::Metrics;
+use crate::system::resource::Resource;
+use crate::system::scheduler::{Scheduler, TaskId};
+use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
+use crate::utils::arch::{critical_section, DWT, SYST};
+use crate::utils::kernel_cell::KernelCell;
+use crate::KernelError;
+
+/// Global instance of Metrics
+static METRICS: KernelCell<Metrics> = KernelCell::new(Metrics::new());
+
+/// Called on every SysTick interrupt. The cycle counter is used as a free-running time reference
+/// to detect ticks that were lost because an interrupt handler ran longer than a tick period.
+pub fn record_tick() {
+    let curr_cycles = DWT::get_cycle_count();
+    let tick_cycles = SYST::get_reload() + 1;
+    critical_section(|cs_token| {
+        METRICS
+            .borrow(cs_token)
+            .borrow_mut()
+            .record_tick(curr_cycles, tick_cycles)
+    })
+}
+
+/// Returns the number of SysTick interrupts lost since the timer was started.
+pub fn lost_ticks() -> u32 {
+    critical_section(|cs_token| METRICS.borrow(cs_token).borrow().lost_ticks())
+}
+
+/// Returns the longest time, in ticks, that a task of higher priority than the holder of `resource`
+/// was kept waiting by it. This is the priority inversion bounded by the ceiling protocol, a value
+/// above the analyzed bound points to wrong ceilings or critical sections longer than assumed.
+pub fn max_inversion<T>(resource: &Resource<T>) -> u32 {
+    resource.max_inversion()
+}
+
+/// Returns the longest time, in ticks, that any task has held `resource` locked. Long hold times
+/// cause the inversions reported by `max_inversion`, hence they point to the critical sections to
+/// shorten.
+pub fn max_hold_time<T>(resource: &Resource<T>) -> u32 {
+    resource.max_hold_time()
+}
+
+/// Returns the number of times the task `tid` has been dispatched, that is, has become the running
+/// task. Unlike the runtime in ticks, this reveals tasks which are switched in and out constantly.
+pub fn dispatch_count(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<u32, KernelError> {
+    if tid as usize >= MAX_TASKS {
+        return Err(KernelError::NotFound);
+    }
+    Ok(critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let count = task_manager.borrow(cs_token).borrow().dispatch_counts[tid as usize];
+        spinunlock(&TASKMANAGER_LOCK);
+        count
+    }))
+}
+
+/// Resets the dispatch counts of all the tasks.
+pub fn reset_dispatch_counts(task_manager: &'static KernelCell<Scheduler>) {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        task_manager.borrow(cs_token).borrow_mut().dispatch_counts = [0; MAX_TASKS];
+        spinunlock(&TASKMANAGER_LOCK);
+    })
+}
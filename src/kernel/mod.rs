@@ -2,6 +2,8 @@
 
 pub mod tasks;
 
+pub mod diagnostics;
+
 #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
 pub mod events;
 
@@ -13,3 +15,12 @@ pub mod task_monitor;
 
 #[cfg(feature = "timer")]
 pub mod timer;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "embedded_hal")]
+pub mod delay;
+
+#[cfg(feature = "self_test")]
+pub mod self_test;
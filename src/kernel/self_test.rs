@@ -0,0 +1,122 @@
+//! # Self test
+//! Checks that a context switch round-trips the registers of a task, so that a broken port of
+//! `save_context` and `load_context` is caught at startup rather than as corruption later on.
+
+use crate::kernel::tasks::{destroy_task, join, release, task_exit};
+use crate::priv_execute;
+use crate::system::scheduler::{Scheduler, TaskId};
+use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
+use crate::utils::arch::{critical_section, is_privileged, yield_with_pattern};
+use crate::utils::kernel_cell::KernelCell;
+use crate::KernelError;
+
+/// The registers loaded by the task which checks its registers.
+const CHECK_PATTERN: u32 = 0x5A5A_0000;
+/// The registers loaded by the task which clobbers the registers.
+const CLOBBER_PATTERN: u32 = 0xA5A5_0000;
+/// Deadline of the test tasks with the task monitor, as for the idle task.
+#[cfg(feature = "task_monitor")]
+const TEST_DEADLINE: u32 = 100;
+
+/// The task manager running the test and the TaskId of the task which clobbers the registers.
+static SELF_TEST: KernelCell<Option<(&'static KernelCell<Scheduler>, TaskId)>> = KernelCell::new(None);
+/// The bits in which the registers of the checking task differed after the round trip.
+static MISMATCH: KernelCell<u32> = KernelCell::new(0);
+
+/// Runs the self test of the context switch and returns `KernelError::ContextCorrupted` if it
+/// fails. The tasks `check_tid` and `clobber_tid` are created, `clobber_tid` being the higher
+/// priority: the first loads known values into r4 to r11 and yields to the second, which loads other
+/// values and exits, the first then verifies its values survived the round trip. The calling task
+/// waits for the test to finish, after which both tasks are destroyed again.
+///
+/// Must be called by a privileged task once the kernel has started, while no reaper is set, and
+/// both priorities must be free.
+pub fn self_test(
+    task_manager: &'static KernelCell<Scheduler>,
+    check_tid: TaskId,
+    clobber_tid: TaskId,
+) -> Result<(), KernelError> {
+    static mut CHECK_STACK: [u32; 128] = [0; 128];
+    static mut CLOBBER_STACK: [u32; 128] = [0; 128];
+
+    if check_tid >= clobber_tid {
+        return Err(KernelError::LimitExceeded);
+    }
+    priv_execute!({
+        critical_section(|cs_token| {
+            *SELF_TEST.borrow(cs_token).borrow_mut() = Some((task_manager, clobber_tid));
+            *MISMATCH.borrow(cs_token).borrow_mut() = 0;
+            spinlock(&TASKMANAGER_LOCK);
+            let handler = &mut task_manager.borrow(cs_token).borrow_mut();
+            let res = if handler.task_control_blocks[check_tid as usize].is_some()
+                || handler.task_control_blocks[clobber_tid as usize].is_some()
+            {
+                Err(KernelError::Exists)
+            } else {
+                let check_stack = unsafe { &mut *core::ptr::addr_of_mut!(CHECK_STACK) };
+                let clobber_stack = unsafe { &mut *core::ptr::addr_of_mut!(CLOBBER_STACK) };
+                create(handler, check_tid, check_stack, check_task)
+                    .and_then(|_| create(handler, clobber_tid, clobber_stack, clobber_task))
+            };
+            spinunlock(&TASKMANAGER_LOCK);
+            res
+        })?;
+        release(task_manager, 1 << check_tid);
+        join(task_manager, check_tid)?;
+        destroy_task(task_manager, check_tid)?;
+        destroy_task(task_manager, clobber_tid)?;
+        match critical_section(|cs_token| *MISMATCH.borrow(cs_token).borrow()) {
+            0 => Ok(()),
+            _ => Err(KernelError::ContextCorrupted),
+        }
+    })
+}
+
+#[cfg(not(feature = "task_monitor"))]
+fn create(
+    handler: &mut Scheduler,
+    tid: TaskId,
+    stack: &mut [u32],
+    handler_fn: fn() -> !,
+) -> Result<(), KernelError> {
+    handler.create_task(tid as usize, stack, handler_fn)
+}
+
+#[cfg(feature = "task_monitor")]
+fn create(
+    handler: &mut Scheduler,
+    tid: TaskId,
+    stack: &mut [u32],
+    handler_fn: fn() -> !,
+) -> Result<(), KernelError> {
+    handler.create_task(tid as usize, TEST_DEADLINE, stack, handler_fn)
+}
+
+fn test_setup() -> (&'static KernelCell<Scheduler>, TaskId) {
+    critical_section(|cs_token| SELF_TEST.borrow(cs_token).borrow().unwrap())
+}
+
+/// Releases the clobbering task, which preempts it on the yield, and records the registers which
+/// were not restored.
+fn check_task() -> ! {
+    let (task_manager, clobber_tid) = test_setup();
+    release(task_manager, 1 << clobber_tid);
+    let mismatch = yield_with_pattern(CHECK_PATTERN);
+    critical_section(|cs_token| *MISMATCH.borrow(cs_token).borrow_mut() = mismatch);
+    loop {
+        task_exit(task_manager);
+    }
+}
+
+/// Exits before it overwrites the registers, so that the yield switches back to the checking task
+/// with the clobbered registers.
+fn clobber_task() -> ! {
+    let (task_manager, _) = test_setup();
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        task_manager.borrow(cs_token).borrow_mut().exit();
+        spinunlock(&TASKMANAGER_LOCK);
+    });
+    yield_with_pattern(CLOBBER_PATTERN);
+    loop {}
+}
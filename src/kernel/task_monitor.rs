@@ -1,20 +1,20 @@
-use core::cell::RefCell;
-
+use crate::config::MAX_TASKS;
 use crate::kernel::timer::get_time;
 use crate::priv_execute;
 use crate::system::scheduler::*;
-use crate::system::task_monitor::TaskMonitor;
+use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
+use crate::system::task_monitor::{DeadlineKind, TaskMonitor};
 use crate::utils::arch::is_privileged;
-use crate::utils::arch::{critical_section, svc_call, Mutex};
+use crate::utils::arch::{critical_section, svc_call};
+use crate::utils::kernel_cell::KernelCell;
 use crate::KernelError;
 
-static TASK_MONITOR: Mutex<spin::Mutex<RefCell<TaskMonitor>>> = Mutex::new(spin::Mutex::new(RefCell::new(TaskMonitor::new())));
+static TASK_MONITOR: KernelCell<TaskMonitor> = KernelCell::new(TaskMonitor::new());
 
 pub fn set_deadline(tid: TaskId, deadline: u32) {
     critical_section(|cs_token| {
         TASK_MONITOR
             .borrow(cs_token)
-            .lock()
             .borrow_mut()
             .set_deadline(tid, get_time() + deadline);
     })
@@ -24,28 +24,153 @@ pub fn set_handler(handler: fn()) {
     critical_section(|cs_token| {
         TASK_MONITOR
             .borrow(cs_token)
-            .lock()
             .borrow_mut()
             .set_handler(handler);
     })
 }
 
+/// Returns the number of ticks left until the deadline of the current release of the task `tid`,
+/// `None` if the task has no pending deadline. A deadline which has been reached reports 0.
+pub fn time_to_deadline(tid: TaskId) -> Option<u32> {
+    if tid as usize >= MAX_TASKS {
+        return None;
+    }
+    let curr_time = get_time();
+    let deadline = critical_section(|cs_token| TASK_MONITOR.borrow(cs_token).borrow().deadline(tid))?;
+    Some((deadline.wrapping_sub(curr_time) as i32).max(0) as u32)
+}
+
+/// Marks the deadline of the task `tid` as hard or soft, deadlines are hard by default. A missed
+/// hard deadline calls the deadline exceed handler, a missed soft deadline is only counted.
+pub fn set_deadline_kind(tid: TaskId, kind: DeadlineKind) -> Result<(), KernelError> {
+    if tid as usize >= MAX_TASKS {
+        return Err(KernelError::NotFound);
+    }
+    critical_section(|cs_token| {
+        TASK_MONITOR
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_deadline_kind(tid, kind);
+    });
+    Ok(())
+}
+
+/// Returns the number of soft deadlines the task `tid` has missed.
+pub fn soft_misses(tid: TaskId) -> Result<u32, KernelError> {
+    if tid as usize >= MAX_TASKS {
+        return Err(KernelError::NotFound);
+    }
+    Ok(critical_section(|cs_token| {
+        TASK_MONITOR.borrow(cs_token).borrow().soft_misses(tid)
+    }))
+}
+
+/// Declares the worst-case execution time of the task `tid` in ticks. The runtime of a task is
+/// sampled on every tick, if it exceeds the WCET the overrun handler is called.
+pub fn set_wcet(tid: TaskId, wcet: u32) -> Result<(), KernelError> {
+    if tid as usize >= MAX_TASKS {
+        return Err(KernelError::NotFound);
+    }
+    critical_section(|cs_token| {
+        TASK_MONITOR
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_wcet(tid, wcet);
+    });
+    Ok(())
+}
+
+/// Sets the handler called with the TaskId of a task that runs longer than its declared WCET.
+pub fn set_overrun_handler(handler: fn(TaskId)) {
+    critical_section(|cs_token| {
+        TASK_MONITOR
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_overrun_handler(handler);
+    })
+}
+
+/// Charges the current tick to the currently running task.
+pub fn account_tick(task_manager: &'static KernelCell<Scheduler>) {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = task_manager.borrow(cs_token).borrow();
+        let (started, curr_tid) = (handler.started, handler.curr_tid);
+        drop(handler);
+        spinunlock(&TASKMANAGER_LOCK);
+        if started {
+            TASK_MONITOR
+                .borrow(cs_token)
+                .borrow_mut()
+                .account_tick(curr_tid as TaskId);
+        }
+    })
+}
+
+/// Copies the runtime of every task since its last release, in ticks, into `runtimes`. All the
+/// runtimes are read in a single critical section, hence they are consistent with each other.
+pub fn runtime_snapshot(runtimes: &mut [u32; MAX_TASKS]) {
+    critical_section(|cs_token| {
+        runtimes.copy_from_slice(TASK_MONITOR.borrow(cs_token).borrow().runtimes());
+    })
+}
+
 pub fn clear_deadline(tid: TaskId) {
     critical_section(|cs_token| {
         TASK_MONITOR
             .borrow(cs_token)
-            .lock()
             .borrow_mut()
             .clear_deadline(tid);
     })
 }
 
-pub fn sweep_deadlines() {
+/// Moves the deadline, the deadline kind, the WCET and the runtime of the task `from` to the task
+/// `to`, called when the priority of a task is changed.
+pub fn move_task(from: TaskId, to: TaskId) {
     critical_section(|cs_token| {
         TASK_MONITOR
             .borrow(cs_token)
-            .lock()
             .borrow_mut()
-            .sweep_deadlines(get_time());
+            .move_task(from, to);
+    })
+}
+
+/// Sets the handler called with the TaskId of every task which misses its deadline, hard or soft.
+/// The handler runs in the SysTick interrupt once the sweep of the deadlines is complete, hence it
+/// sees a consistent monitor state and can call the task monitor routines, but it must be short as
+/// it delays the tick.
+pub fn on_deadline_miss(handler: fn(TaskId)) {
+    critical_section(|cs_token| {
+        TASK_MONITOR
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_miss_handler(handler);
+    })
+}
+
+pub fn sweep_deadlines() {
+    let (missed, miss_handler) = critical_section(|cs_token| {
+        let monitor = &mut *TASK_MONITOR.borrow(cs_token).borrow_mut();
+        (monitor.sweep_deadlines(get_time()), monitor.miss_handler())
+    });
+    if let Some(handler) = miss_handler {
+        for tid in 0..MAX_TASKS {
+            if missed & (1 << tid) > 0 {
+                handler(tid as TaskId);
+            }
+        }
+    }
+}
+
+/// Returns the number of ticks elapsed since the currently running task was last released,
+/// 0 if the task was never released.
+pub fn time_since_release(task_manager: &'static KernelCell<Scheduler>) -> u32 {
+    let curr_time = get_time();
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = task_manager.borrow(cs_token).borrow();
+        let release_time = handler.release_times[handler.curr_tid];
+        spinunlock(&TASKMANAGER_LOCK);
+        release_time.map_or(0, |release_time| curr_time.wrapping_sub(release_time))
     })
 }
@@ -2,16 +2,20 @@
 //! Defines Kernel routines which will take care of Task management functionality.
 //! Declares a global instance of Scheduler that will be used by the Kernel routines to provide the functionality.
 
-use core::cell::RefCell;
-
+use crate::config::MAX_TASKS;
 use crate::priv_execute;
 use crate::system::scheduler::*;
-use crate::utils::arch::is_privileged;
-use crate::utils::arch::{critical_section, set_pendsv, svc_call, Mutex};
 use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
+use crate::utils::arch;
+use crate::utils::arch::is_privileged;
+use crate::utils::arch::{critical_section, set_pendsv, svc_call, svc_yield, SleepDepth};
+use crate::utils::kernel_cell::KernelCell;
 use crate::KernelError;
 use cortex_m_semihosting::hprintln;
 
+#[cfg(feature = "timer")]
+use crate::kernel::timer::get_time;
+
 #[cfg(feature = "system_logger")]
 use crate::kernel::logging;
 #[cfg(feature = "system_logger")]
@@ -19,74 +23,381 @@ use crate::system::system_logger::LogEventType;
 
 /// Global Scheduler instance
 #[no_mangle]
-pub static TaskManager: Mutex<RefCell<Scheduler>> = Mutex::new(RefCell::new(Scheduler::new()));
+pub static TaskManager: KernelCell<Scheduler> = KernelCell::new(Scheduler::new());
+#[cfg(feature = "multicore")]
 #[no_mangle]
-pub static TaskManager_C1: Mutex<RefCell<Scheduler>> = Mutex::new(RefCell::new(Scheduler::new()));
+pub static TaskManager_C1: KernelCell<Scheduler> = KernelCell::new(Scheduler::new());
 
 /// Initializes the Kernel scheduler and creates the idle task, a task that puts the CPU to sleep in a loop.
 /// The idle task is created with zero priority; hence, it is only executed when no other task is in Ready state.
-pub fn init(task_manager: &'static Mutex<RefCell<Scheduler>>, mut stack: &mut [u32]) -> Result<(), KernelError> {
+pub fn init(
+    task_manager: &'static KernelCell<Scheduler>,
+    mut stack: &mut [u32],
+) -> Result<(), KernelError> {
+    arch::enable_event_on_pending();
+    #[cfg(feature = "basepri")]
+    arch::set_kernel_priorities();
     critical_section(|cs_token| task_manager.borrow(cs_token).borrow_mut().init(&mut stack))
 }
 
+/// Initializes the Kernel scheduler without creating the idle task, which frees priority 0 for the
+/// application. The application must guarantee that at least one task is always ready, else the
+/// scheduler has no task to dispatch.
+pub fn init_no_idle(task_manager: &'static KernelCell<Scheduler>, is_preemptive: bool) {
+    #[cfg(feature = "basepri")]
+    arch::set_kernel_priorities();
+    critical_section(|cs_token| {
+        task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .init_no_idle(is_preemptive)
+    })
+}
+
+/// Sets the hook which the idle task calls on every iteration, the returned `SleepDepth` decides
+/// how deep the CPU sleeps until the next interrupt. The hook replaces the default sleep of the idle
+/// task, which is `wfi`, or `wfe` with the `idle_wfe` feature. A hook returning `SleepDepth::None`
+/// takes over the idle behavior entirely, e.g. to feed a watchdog or to enter a vendor specific low
+/// power mode itself.
+///
+/// The hook runs in the idle task like the code of any other task, in thread mode with the privilege
+/// of the tasks and with interrupts enabled. Hence it is preempted by every interrupt and by every
+/// task which becomes ready.
+pub fn set_idle_hook(hook: fn() -> SleepDepth) {
+    arch::set_idle_hook(hook)
+}
+
+/// Sets the handler called when a task is found to have overflowed its stack on a context switch.
+/// The canary at the bottom of the outgoing task's stack is checked on every context switch.
+/// If no handler is set, the kernel panics.
+#[cfg(feature = "stack_check")]
+pub fn set_stack_corruption_handler(handler: fn(TaskId)) {
+    arch::set_stack_corruption_handler(handler)
+}
+
+/// Returns the highest number of words of its stack the task `tid` has used so far, which is the
+/// stack size the task needs, not counting the interrupts which have not nested on it yet.
+#[cfg(feature = "stack_check")]
+pub fn stack_usage(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<usize, KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow()
+            .stack_usage(tid as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })
+}
+
+/// Sets the handler called for the SVC immediates which are not used by the kernel, this allows the
+/// application to define its own supervisor calls. The handler gets the immediate of the SVC.
+pub fn set_svc_fallback(handler: fn(u8)) {
+    arch::set_svc_fallback(handler)
+}
+
+/// Sets the hook which is called right before the CPU sleeps, the CPU sleeps only if the hook
+/// returns true. The hook runs with interrupts masked.
+pub fn set_pre_sleep_hook(hook: fn() -> bool) {
+    arch::set_pre_sleep_hook(hook)
+}
+
 /// Starts the Kernel scheduler, which starts scheduling tasks on the CPU.
-pub fn start_kernel(task_manager: &'static Mutex<RefCell<Scheduler>>) -> ! {
+/// The first task to run is the highest priority task in the ready state, irrespective of the order
+/// in which tasks were created (see `first_task`). Panics if a registered resource is
+/// misconfigured (see `resources::validate`).
+pub fn start_kernel(task_manager: &'static KernelCell<Scheduler>) -> ! {
+    if let Err(err) = crate::system::resource::validate() {
+        panic!("invalid resource configuration: {:?}", err);
+    }
     loop {
         schedule(task_manager);
     }
 }
 
 #[cfg(feature = "task_monitor")]
-/// Create a new task with the configuration set as arguments passed.
+/// Create a new task with the configuration set as arguments passed, returns a handle to the task.
+/// Tasks can also be created by a running task, which must be privileged (see `with_privilege`).
+/// Returns `KernelError::Exists` if a task of priority `priority` already exists.
 pub fn create_task(
     priority: TaskId,
     deadline: u32,
     stack: &mut [u32],
     handler_fn: fn() -> !,
-) -> Result<(), KernelError> {
+) -> Result<TaskHandle, KernelError> {
     priv_execute!({
         critical_section(|cs_token| {
-            TaskManager.borrow(cs_token).borrow_mut().create_task(
+            spinlock(&TASKMANAGER_LOCK);
+            let res = TaskManager.borrow(cs_token).borrow_mut().create_task(
                 priority as usize,
                 deadline,
                 stack,
                 handler_fn,
-            )
-        })
+            );
+            spinunlock(&TASKMANAGER_LOCK);
+            res
+        })?;
+        reschedule_if_started(&TaskManager);
+        Ok(TaskHandle::new(&TaskManager, priority))
     })
 }
 
 #[cfg(not(feature = "task_monitor"))]
-/// Create a new task with the configuration set as arguments passed.
+/// Create a new task with the configuration set as arguments passed, returns a handle to the task.
+/// Tasks can also be created by a running task, which must be privileged (see `with_privilege`).
+/// Returns `KernelError::Exists` if a task of priority `priority` already exists.
 pub fn create_task(
-    task_manager: &'static Mutex<RefCell<Scheduler>>,
+    task_manager: &'static KernelCell<Scheduler>,
     priority: TaskId,
     stack: &mut [u32],
     handler_fn: fn() -> !,
-) -> Result<(), KernelError> {
+) -> Result<TaskHandle, KernelError> {
     priv_execute!({
         critical_section(|cs_token| {
-            task_manager.borrow(cs_token).borrow_mut().create_task(
+            spinlock(&TASKMANAGER_LOCK);
+            let res = task_manager.borrow(cs_token).borrow_mut().create_task(
                 priority as usize,
                 stack,
                 handler_fn,
-            )
+            );
+            spinunlock(&TASKMANAGER_LOCK);
+            res
+        })?;
+        reschedule_if_started(task_manager);
+        Ok(TaskHandle::new(task_manager, priority))
+    })
+}
+
+/// Allows the task `tid` to raise its privilege with `with_privilege`, tasks are not allowed to by
+/// default. Meant to be called right after the task is created, the caller must be privileged.
+pub fn allow_privilege(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<(), KernelError> {
+    priv_execute!({
+        critical_section(|cs_token| {
+            spinlock(&TASKMANAGER_LOCK);
+            let res = task_manager
+                .borrow(cs_token)
+                .borrow_mut()
+                .set_may_elevate(tid as usize, true);
+            spinunlock(&TASKMANAGER_LOCK);
+            res
         })
     })
 }
 
+/// A task created while the kernel runs may have been released before it was created, in which
+/// case it can preempt the running task right away.
+fn reschedule_if_started(task_manager: &'static KernelCell<Scheduler>) {
+    let started = critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let started = task_manager.borrow(cs_token).borrow().started;
+        spinunlock(&TASKMANAGER_LOCK);
+        started
+    });
+    if started {
+        schedule(task_manager);
+    }
+}
+
+#[cfg(all(feature = "timer", feature = "task_monitor"))]
+/// Creates a task which is released every `period` ticks by the kernel timer, the first release
+/// being a period from now. The deadline of every release is the next period, hence a release which
+/// has not finished by then is reported as a deadline miss by the task monitor.
+pub fn create_periodic_task(
+    priority: TaskId,
+    period: u32,
+    stack: &mut [u32],
+    handler_fn: fn() -> !,
+) -> Result<TaskHandle, KernelError> {
+    let handle = create_task(priority, period, stack, handler_fn)?;
+    set_period(&TaskManager, priority, period)?;
+    Ok(handle)
+}
+
+#[cfg(all(feature = "timer", not(feature = "task_monitor")))]
+/// Creates a task which is released every `period` ticks by the kernel timer, the first release
+/// being a period from now.
+pub fn create_periodic_task(
+    task_manager: &'static KernelCell<Scheduler>,
+    priority: TaskId,
+    period: u32,
+    stack: &mut [u32],
+    handler_fn: fn() -> !,
+) -> Result<TaskHandle, KernelError> {
+    let handle = create_task(task_manager, priority, stack, handler_fn)?;
+    set_period(task_manager, priority, period)?;
+    Ok(handle)
+}
+
+#[cfg(all(feature = "timer", feature = "task_monitor"))]
+/// Same as `create_task`, but if a task of priority `priority` already exists, the calling task
+/// blocks until its slot is freed by `destroy_task` and then creates the task. Returns
+/// `KernelError::Timeout` if the slot is not freed within `max_ticks` ticks.
+pub fn create_task_wait(
+    priority: TaskId,
+    deadline: u32,
+    stack: &mut [u32],
+    handler_fn: fn() -> !,
+    max_ticks: u32,
+) -> Result<TaskHandle, KernelError> {
+    let wake_time = get_time().wrapping_add(max_ticks);
+    loop {
+        match create_task(priority, deadline, stack, handler_fn) {
+            Err(KernelError::Exists) => wait_for_slot(&TaskManager, priority, wake_time)?,
+            res => return res,
+        }
+    }
+}
+
+#[cfg(all(feature = "timer", not(feature = "task_monitor")))]
+/// Same as `create_task`, but if a task of priority `priority` already exists, the calling task
+/// blocks until its slot is freed by `destroy_task` and then creates the task. Returns
+/// `KernelError::Timeout` if the slot is not freed within `max_ticks` ticks.
+pub fn create_task_wait(
+    task_manager: &'static KernelCell<Scheduler>,
+    priority: TaskId,
+    stack: &mut [u32],
+    handler_fn: fn() -> !,
+    max_ticks: u32,
+) -> Result<TaskHandle, KernelError> {
+    let wake_time = get_time().wrapping_add(max_ticks);
+    loop {
+        match create_task(task_manager, priority, stack, handler_fn) {
+            Err(KernelError::Exists) => wait_for_slot(task_manager, priority, wake_time)?,
+            res => return res,
+        }
+    }
+}
+
+/// Blocks the currently running task until the slot `tid` is freed or until `wake_time`, returns
+/// `KernelError::Timeout` if `wake_time` has been reached.
+#[cfg(feature = "timer")]
+fn wait_for_slot(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+    wake_time: u32,
+) -> Result<(), KernelError> {
+    let curr_time = get_time();
+    let is_waiting = critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager.borrow(cs_token).borrow_mut().wait_for_slot(
+            tid as usize,
+            wake_time,
+            curr_time,
+        );
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })?;
+    if !is_waiting {
+        return Err(KernelError::Timeout);
+    }
+    schedule(task_manager);
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .stop_waiting_for_slot(tid as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+    });
+    Ok(())
+}
+
+#[cfg(feature = "timer")]
+fn set_period(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+    period: u32,
+) -> Result<(), KernelError> {
+    let first_release = get_time().wrapping_add(period);
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager.borrow(cs_token).borrow_mut().set_period(
+            tid as usize,
+            period,
+            first_release,
+        );
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })
+}
+
+/// Returns the number of periods the periodic task `tid` was still running when its next period
+/// arrived.
+#[cfg(feature = "timer")]
+pub fn missed_periods(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<u32, KernelError> {
+    if tid as usize >= MAX_TASKS {
+        return Err(KernelError::NotFound);
+    }
+    Ok(critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let missed = task_manager.borrow(cs_token).borrow().missed_periods[tid as usize];
+        spinunlock(&TASKMANAGER_LOCK);
+        missed
+    }))
+}
+
+/// Handle to a task created by `create_task`, which builds the task masks for the kernel routines
+/// operating on the task.
+#[derive(Clone, Copy)]
+pub struct TaskHandle {
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+}
+
+impl TaskHandle {
+    pub const fn new(task_manager: &'static KernelCell<Scheduler>, tid: TaskId) -> Self {
+        Self { task_manager, tid }
+    }
+
+    /// Returns the TaskId of the task, which is also its priority.
+    pub fn tid(&self) -> TaskId {
+        self.tid
+    }
+
+    fn mask(&self) -> BooleanVector {
+        1 << self.tid
+    }
+
+    /// Releases the task, see `release`.
+    pub fn release(&self) {
+        release(self.task_manager, self.mask())
+    }
+
+    /// Blocks the task, see `block_tasks`.
+    pub fn block(&self) {
+        block_tasks(self.task_manager, self.mask(), BlockReason::Suspended)
+    }
+
+    /// Unblocks the task, see `unblock_tasks`.
+    pub fn unblock(&self) {
+        unblock_tasks(self.task_manager, self.mask(), BlockReason::Suspended)
+    }
+}
+
 /// This function is called from both privileged and unprivileged context.
 /// Hence if the function is called from privileged context, then `preempt()` is called.
 /// Else, the `svc_call()` is executed, this function creates the SVC exception.
 /// And the SVC handler calls schedule again. Thus, the permission level is raised to privileged via the exception.
-pub fn schedule(task_manager: &'static Mutex<RefCell<Scheduler>>) {
-    let is_preemptive =
-        critical_section(|cs_token| {
-            spinlock(&TASKMANAGER_LOCK);
-            let t = task_manager.borrow(cs_token).borrow().is_preemptive;
-            spinunlock(&TASKMANAGER_LOCK);
-            t
-        });
+/// When called from the idle task while no other task is ready, there is nothing to switch to and
+/// it returns right away.
+pub fn schedule(task_manager: &'static KernelCell<Scheduler>) {
+    let is_preemptive = critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = task_manager.borrow(cs_token).borrow();
+        let t = handler.is_preemptive && !handler.is_idle_only();
+        drop(handler);
+        spinunlock(&TASKMANAGER_LOCK);
+        t
+    });
     if is_preemptive {
         match is_privileged() {
             true => preempt(),
@@ -95,13 +406,59 @@ pub fn schedule(task_manager: &'static Mutex<RefCell<Scheduler>>) {
     }
 }
 
+/// Lets a higher priority ready task run without exiting the current task, which stays active and
+/// resumes once it is again the highest priority ready task. It is a no-op if the current task is
+/// the task the scheduler would dispatch anyway.
+///
+/// Unlike `schedule`, the yield does not depend on `is_preemptive`, hence an explicit yield is
+/// honored by a cooperative scheduler (see `init_no_idle`) and within `disable_preemption`.
+/// Preemption threshold of the current task still applies.
+pub fn yield_task(task_manager: &'static KernelCell<Scheduler>) {
+    if pending_preemption(task_manager).is_some() {
+        match is_privileged() {
+            true => preempt(),
+            false => svc_yield(),
+        };
+    }
+}
+
+/// Pends a context switch if a task which should preempt the running task became ready. Called on
+/// exit of the interrupt handlers defined with `kernel_interrupt!`.
+pub fn isr_exit(task_manager: &'static KernelCell<Scheduler>) {
+    if pending_preemption(task_manager).is_some() {
+        schedule(task_manager);
+    }
+}
+
 #[inline(never)]
 fn preempt() {
     set_pendsv();
 }
 
+/// Returns the TaskId of the task that the scheduler will dispatch first, that is, the highest
+/// priority task which is active and not blocked, `None` if no task is ready.
+pub fn first_task(task_manager: &'static KernelCell<Scheduler>) -> Option<TaskId> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let tid = task_manager.borrow(cs_token).borrow().get_next_tid();
+        spinunlock(&TASKMANAGER_LOCK);
+        tid.map(|tid| tid as TaskId)
+    })
+}
+
+/// Returns the TaskId of the ready task which would run instead of the currently running task if
+/// preemption were enabled, `None` if the currently running task would keep running.
+pub fn pending_preemption(task_manager: &'static KernelCell<Scheduler>) -> Option<TaskId> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let tid = task_manager.borrow(cs_token).borrow().pending_preemption();
+        spinunlock(&TASKMANAGER_LOCK);
+        tid.map(|tid| tid as TaskId)
+    })
+}
+
 /// Returns the TaskId of the currently running task in the kernel.
-pub fn get_curr_tid(task_manager: &'static Mutex<RefCell<Scheduler>>) -> TaskId {
+pub fn get_curr_tid(task_manager: &'static KernelCell<Scheduler>) -> TaskId {
     critical_section(|cs_token| {
         spinlock(&TASKMANAGER_LOCK);
         let tid = task_manager.borrow(cs_token).borrow().curr_tid as TaskId;
@@ -110,8 +467,97 @@ pub fn get_curr_tid(task_manager: &'static Mutex<RefCell<Scheduler>>) -> TaskId
     })
 }
 
-/// The Kernel blocks the tasks mentioned in `tasks_mask`.
-pub fn block_tasks(task_manager: &'static Mutex<RefCell<Scheduler>>, tasks_mask: BooleanVector) {
+/// Sets the name of the task `tid`, used for diagnostics.
+pub fn set_task_name(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+    name: &'static str,
+) -> Result<(), KernelError> {
+    if tid as usize >= MAX_TASKS {
+        return Err(KernelError::NotFound);
+    }
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        task_manager.borrow(cs_token).borrow_mut().task_names[tid as usize] = name;
+        spinunlock(&TASKMANAGER_LOCK);
+    });
+    Ok(())
+}
+
+/// Returns the name of the task `tid`, an empty string if the task was not named.
+pub fn task_name(task_manager: &'static KernelCell<Scheduler>, tid: TaskId) -> &'static str {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let name = task_manager
+            .borrow(cs_token)
+            .borrow()
+            .task_names
+            .get(tid as usize)
+            .copied()
+            .unwrap_or("");
+        spinunlock(&TASKMANAGER_LOCK);
+        name
+    })
+}
+
+/// Returns the name of the currently running task.
+pub fn current_task_name(task_manager: &'static KernelCell<Scheduler>) -> &'static str {
+    task_name(task_manager, get_curr_tid(task_manager))
+}
+
+/// Returns the `active_tasks` and `blocked_tasks` boolean vectors, read under a single critical section
+/// so that the pair is consistent.
+pub fn ready_blocked_snapshot(
+    task_manager: &'static KernelCell<Scheduler>,
+) -> (BooleanVector, BooleanVector) {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = task_manager.borrow(cs_token).borrow();
+        let snapshot = (handler.active_tasks, handler.blocked_tasks);
+        spinunlock(&TASKMANAGER_LOCK);
+        snapshot
+    })
+}
+
+/// Returns why the task `tid` is blocked, `None` if it is not blocked or does not exist.
+pub fn block_reason(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Option<BlockReason> {
+    if tid as usize >= MAX_TASKS {
+        return None;
+    }
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let reason = task_manager.borrow(cs_token).borrow().block_reasons[tid as usize];
+        spinunlock(&TASKMANAGER_LOCK);
+        reason
+    })
+}
+
+/// Checks that all the tasks in `tasks_mask` have been created.
+pub fn validate_tasks(
+    task_manager: &'static KernelCell<Scheduler>,
+    tasks_mask: BooleanVector,
+) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow()
+            .validate_tasks(tasks_mask);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })
+}
+
+/// The Kernel blocks the tasks mentioned in `tasks_mask`, `reason` is recorded for each of them
+/// until they are unblocked.
+pub fn block_tasks(
+    task_manager: &'static KernelCell<Scheduler>,
+    tasks_mask: BooleanVector,
+    reason: BlockReason,
+) {
     #[cfg(feature = "system_logger")]
     {
         if logging::get_block_tasks() {
@@ -123,13 +569,18 @@ pub fn block_tasks(task_manager: &'static Mutex<RefCell<Scheduler>>, tasks_mask:
         task_manager
             .borrow(cs_token)
             .borrow_mut()
-            .block_tasks(tasks_mask);
+            .block_tasks(tasks_mask, reason);
         spinunlock(&TASKMANAGER_LOCK);
     })
 }
 
-/// The Kernel unblocks the tasks mentioned in tasks_mask.
-pub fn unblock_tasks(task_manager: &'static Mutex<RefCell<Scheduler>>, tasks_mask: BooleanVector) {
+/// The Kernel unblocks the tasks mentioned in tasks_mask which were blocked for `reason`, the tasks
+/// blocked for another reason stay blocked.
+pub fn unblock_tasks(
+    task_manager: &'static KernelCell<Scheduler>,
+    tasks_mask: BooleanVector,
+    reason: BlockReason,
+) {
     #[cfg(feature = "system_logger")]
     {
         if logging::get_unblock_tasks() {
@@ -141,50 +592,491 @@ pub fn unblock_tasks(task_manager: &'static Mutex<RefCell<Scheduler>>, tasks_mas
         task_manager
             .borrow(cs_token)
             .borrow_mut()
-            .unblock_tasks(tasks_mask);
+            .unblock_tasks(tasks_mask, reason);
         spinunlock(&TASKMANAGER_LOCK);
     })
 }
 
 /// The `task_exit` function is called just after a task finishes execution. It marks the current running task as finished and then schedules the next high priority task.
-pub fn task_exit(task_manager: &'static Mutex<RefCell<Scheduler>>) {
+pub fn task_exit(task_manager: &'static KernelCell<Scheduler>) {
     critical_section(|cs_token| {
         spinlock(&TASKMANAGER_LOCK);
         let handler = &mut task_manager.borrow(cs_token).borrow_mut();
-        let curr_tid = handler.curr_tid;
         #[cfg(feature = "system_logger")]
         {
             if logging::get_task_exit() {
-                logging::report(LogEventType::TaskExit(curr_tid as TaskId));
+                logging::report(LogEventType::TaskExit(handler.curr_tid as TaskId));
+            }
+        }
+        #[allow(unused_variables)]
+        let released = handler.exit();
+        #[cfg(feature = "timer")]
+        handler.stamp_release(released, get_time());
+        spinunlock(&TASKMANAGER_LOCK);
+        #[cfg(feature = "system_logger")]
+        {
+            if released != 0 && logging::get_release() {
+                logging::report(LogEventType::ReleaseTasks(released));
             }
         }
-        handler.active_tasks &= !(1 << curr_tid as u32);
+    });
+    schedule(task_manager)
+}
+
+/// Sets the task `tid` as the reaper. Once a reaper is set, `task_exit` only marks the task as a
+/// zombie and releases the reaper, which must call `reap` to unblock the joiners and release the
+/// successors of the exited tasks, so that the cleanup does not run on the stack of the exiting task.
+/// The idle task cannot be the reaper.
+pub fn set_reaper(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_reaper(tid as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })
+}
+
+/// Cleans up all the tasks which exited since the last call, meant to be called by the reaper task.
+pub fn reap(task_manager: &'static KernelCell<Scheduler>) {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = &mut task_manager.borrow(cs_token).borrow_mut();
+        #[allow(unused_variables)]
+        let released = handler.reap();
+        #[cfg(feature = "timer")]
+        handler.stamp_release(released, get_time());
         spinunlock(&TASKMANAGER_LOCK);
+        #[cfg(feature = "system_logger")]
+        {
+            if released != 0 && logging::get_release() {
+                logging::report(LogEventType::ReleaseTasks(released));
+            }
+        }
     });
     schedule(task_manager)
 }
+
+/// Frees the slot of the exited task `tid`, so that `create_task` can create a new task at its
+/// priority, with a freshly initialized stack. The stack of the destroyed task can then be reused.
+/// Returns `AccessDenied` if the task has not exited, is waiting to be reaped or is the idle task.
+/// The tasks waiting in `create_task_wait` for the slot are unblocked.
+pub fn destroy_task(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .destroy(tid as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })?;
+    reschedule_if_started(task_manager);
+    Ok(())
+}
+
+/// Sets the tasks released when the task `tid` calls `task_exit`, so that tasks can be chained into
+/// a pipeline. The successors must have been created. Returns `KernelError::Cyclic` if the
+/// successors would form a cycle.
+pub fn set_successors(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+    tasks_mask: BooleanVector,
+) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_successors(tid as usize, tasks_mask);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })
+}
+
+/// Blocks the currently running task until the task `tid` calls `task_exit`.
+/// Returns immediately if `tid` is not active. The caller blocks forever if `tid` never exits.
+/// Returns `AccessDenied` if `tid` is the calling task or the idle task.
+pub fn join(task_manager: &'static KernelCell<Scheduler>, tid: TaskId) -> Result<(), KernelError> {
+    let is_waiting = critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .join(tid as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })?;
+    if is_waiting {
+        schedule(task_manager);
+    }
+    Ok(())
+}
+
+/// Blocks the currently running task for `ticks` ticks of the kernel timer, after which it is
+/// unblocked by the SysTick handler. Returns `AccessDenied` if called from the idle task.
+#[cfg(feature = "timer")]
+pub fn sleep(task_manager: &'static KernelCell<Scheduler>, ticks: u32) -> Result<(), KernelError> {
+    let wake_time = get_time().wrapping_add(ticks);
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager.borrow(cs_token).borrow_mut().sleep(wake_time);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })?;
+    #[cfg(feature = "system_logger")]
+    {
+        if logging::get_block_tasks() {
+            logging::report(LogEventType::BlockTasks(1 << get_curr_tid(task_manager)));
+        }
+    }
+    schedule(task_manager);
+    Ok(())
+}
+
+/// Unblocks the sleeping tasks whose wake up time has been reached at `curr_time`.
+#[cfg(feature = "timer")]
+pub(crate) fn wake_sleepers(task_manager: &'static KernelCell<Scheduler>, curr_time: u32) {
+    let tasks_mask = critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let tasks_mask = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .expired_sleepers(curr_time);
+        spinunlock(&TASKMANAGER_LOCK);
+        tasks_mask
+    });
+    if tasks_mask != 0 {
+        #[cfg(feature = "system_logger")]
+        {
+            if logging::get_unblock_tasks() {
+                logging::report(LogEventType::UnblockTasks(tasks_mask));
+            }
+        }
+        schedule(task_manager);
+    }
+}
+
+/// Releases the periodic tasks whose release time has been reached at `curr_time`.
+#[cfg(feature = "timer")]
+pub(crate) fn release_periodic(task_manager: &'static KernelCell<Scheduler>, curr_time: u32) {
+    let tasks_mask = critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let tasks_mask = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .due_periodic(curr_time);
+        spinunlock(&TASKMANAGER_LOCK);
+        tasks_mask
+    });
+    if tasks_mask != 0 {
+        release(task_manager, tasks_mask);
+        schedule(task_manager);
+    }
+}
+
 /// The Kernel releases the tasks in the `task_mask`, these tasks transition from the waiting to the ready state.
-pub fn release(task_manager: &'static Mutex<RefCell<Scheduler>>, tasks_mask: BooleanVector) {
+/// All the tasks of the mask are made ready in a single critical section and no context switch
+/// happens in between, hence the release of a collected mask is a single scheduling point: once the
+/// scheduler runs, the highest priority task of the mask is dispatched first and the others follow
+/// in priority order.
+pub fn release(task_manager: &'static KernelCell<Scheduler>, tasks_mask: BooleanVector) {
     #[cfg(feature = "system_logger")]
     {
         if logging::get_release() {
             logging::report(LogEventType::ReleaseTasks(tasks_mask));
         }
     }
+    #[cfg(feature = "timer")]
+    let curr_time = get_time();
     critical_section(|cs_token| {
         spinlock(&TASKMANAGER_LOCK);
-        task_manager
-            .borrow(cs_token)
-            .borrow_mut()
-            .release(tasks_mask);
+        let handler = &mut task_manager.borrow(cs_token).borrow_mut();
+        handler.release(tasks_mask);
+        #[cfg(feature = "timer")]
+        handler.stamp_release(tasks_mask, curr_time);
         spinunlock(&TASKMANAGER_LOCK);
     });
     // TODO: not sure if this needs to enabled or not?
     // schedule(task_manager);
 }
 
+/// Replaces the set of released tasks with `tasks_mask` and reschedules. Unlike `release`, tasks
+/// which are not in `tasks_mask` stop being active. The idle task cannot be removed from the set.
+pub fn set_ready_set(task_manager: &'static KernelCell<Scheduler>, tasks_mask: BooleanVector) {
+    #[cfg(feature = "timer")]
+    let curr_time = get_time();
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = &mut task_manager.borrow(cs_token).borrow_mut();
+        #[allow(unused_variables)]
+        let released = handler.set_active_tasks(tasks_mask);
+        #[cfg(feature = "timer")]
+        handler.stamp_release(released, curr_time);
+        spinunlock(&TASKMANAGER_LOCK);
+        #[cfg(feature = "system_logger")]
+        {
+            if logging::get_release() {
+                logging::report(LogEventType::ReleaseTasks(released));
+            }
+        }
+    });
+    schedule(task_manager);
+}
+
+/// Same as `release`, but the release is logged even when release logging is disabled.
+#[cfg(feature = "system_logger")]
+pub fn release_traced(task_manager: &'static KernelCell<Scheduler>, tasks_mask: BooleanVector) {
+    if !logging::get_release() {
+        logging::report(LogEventType::ReleaseTasks(tasks_mask));
+    }
+    release(task_manager, tasks_mask);
+}
+
+/// Releases the tasks in `tasks_mask` which were not released within the last `min_interval` ticks,
+/// releases that come too soon are dropped. Returns the tasks that were actually released.
+#[cfg(feature = "timer")]
+pub fn release_rate_limited(
+    task_manager: &'static KernelCell<Scheduler>,
+    tasks_mask: BooleanVector,
+    min_interval: u32,
+) -> BooleanVector {
+    let curr_time = get_time();
+    let released = critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = &mut task_manager.borrow(cs_token).borrow_mut();
+        let released = handler.rate_limit(tasks_mask, min_interval, curr_time);
+        handler.release(released);
+        handler.stamp_release(released, curr_time);
+        spinunlock(&TASKMANAGER_LOCK);
+        released
+    });
+    #[cfg(feature = "system_logger")]
+    {
+        if released != 0 && logging::get_release() {
+            logging::report(LogEventType::ReleaseTasks(released));
+        }
+    }
+    released
+}
+
+/// Moves the task at priority `from` to the unoccupied priority `to` and reschedules. Returns
+/// `NotFound` if there is no task at `from` and `Exists` if `to` is occupied. The idle task cannot
+/// be moved and no task can be moved to its priority, nor in or out of a round-robin band.
+/// Resources and other primitives hold task masks of their own, these have to be set up for the
+/// new priority by the application.
+pub fn change_priority(
+    task_manager: &'static KernelCell<Scheduler>,
+    from: TaskId,
+    to: TaskId,
+) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .change_priority(from as usize, to as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })?;
+    schedule(task_manager);
+    Ok(())
+}
+
+/// Sets the preemption threshold of the task `tid`, once running, the task can be preempted only by
+/// tasks with a priority higher than `threshold`.
+pub fn set_preempt_threshold(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+    threshold: TaskId,
+) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_preempt_threshold(tid as usize, threshold as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })
+}
+
+/// Returns the preemption threshold of the task `tid`.
+pub fn get_preempt_threshold(
+    task_manager: &'static KernelCell<Scheduler>,
+    tid: TaskId,
+) -> Result<TaskId, KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow()
+            .get_preempt_threshold(tid as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res.map(|threshold| threshold as TaskId)
+    })
+}
+
+/// Returns a `Transaction` which queues kernel operations and applies them atomically on `commit`.
+pub fn transaction(task_manager: &'static KernelCell<Scheduler>) -> Transaction {
+    Transaction {
+        task_manager,
+        release_mask: 0,
+        block_mask: 0,
+        unblock_mask: 0,
+    }
+}
+
+/// Queues release, block and unblock operations which are applied under a single critical section,
+/// followed by a single call to `schedule`. Hence no other task observes an intermediate state.
+/// A task that is both blocked and unblocked in a transaction ends up in the state of the last call.
+#[must_use]
+pub struct Transaction {
+    task_manager: &'static KernelCell<Scheduler>,
+    release_mask: BooleanVector,
+    block_mask: BooleanVector,
+    unblock_mask: BooleanVector,
+}
+
+impl Transaction {
+    /// Queues the release of the tasks in `tasks_mask`.
+    pub fn release(mut self, tasks_mask: BooleanVector) -> Self {
+        self.release_mask |= tasks_mask;
+        self
+    }
+
+    /// Queues blocking the tasks in `tasks_mask`.
+    pub fn block(mut self, tasks_mask: BooleanVector) -> Self {
+        self.block_mask |= tasks_mask;
+        self.unblock_mask &= !tasks_mask;
+        self
+    }
+
+    /// Queues unblocking the tasks in `tasks_mask`, only the tasks which were blocked explicitly are
+    /// unblocked.
+    pub fn unblock(mut self, tasks_mask: BooleanVector) -> Self {
+        self.unblock_mask |= tasks_mask;
+        self.block_mask &= !tasks_mask;
+        self
+    }
+
+    /// Applies all the queued operations and then schedules.
+    pub fn commit(self) {
+        #[cfg(feature = "system_logger")]
+        {
+            if self.release_mask != 0 && logging::get_release() {
+                logging::report(LogEventType::ReleaseTasks(self.release_mask));
+            }
+            if self.block_mask != 0 && logging::get_block_tasks() {
+                logging::report(LogEventType::BlockTasks(self.block_mask));
+            }
+            if self.unblock_mask != 0 && logging::get_unblock_tasks() {
+                logging::report(LogEventType::UnblockTasks(self.unblock_mask));
+            }
+        }
+        #[cfg(feature = "timer")]
+        let curr_time = get_time();
+        critical_section(|cs_token| {
+            spinlock(&TASKMANAGER_LOCK);
+            let handler = &mut self.task_manager.borrow(cs_token).borrow_mut();
+            handler.release(self.release_mask);
+            #[cfg(feature = "timer")]
+            handler.stamp_release(self.release_mask, curr_time);
+            handler.block_tasks(self.block_mask, BlockReason::Suspended);
+            handler.unblock_tasks(self.unblock_mask, BlockReason::Suspended);
+            spinunlock(&TASKMANAGER_LOCK);
+        });
+        schedule(self.task_manager);
+    }
+}
+
+/// Freezes the scheduler until the returned guard is dropped or `exit_maintenance` is called, so that
+/// the calling task, usually the highest priority task, can rewrite flash without being switched out.
+/// Unlike `disable_preemption`, explicit yields are frozen as well.
+///
+/// While in maintenance mode, every interrupt remains enabled and the kernel bookkeeping goes on:
+/// SysTick still counts the time, raises the timer events, wakes up the sleeping tasks, releases the
+/// periodic tasks and sweeps the deadlines, and tasks are still released and unblocked. PendSV still
+/// runs, but it always dispatches the running task. Hence the tasks made ready only run once the
+/// maintenance mode is left. The running task must not block, sleep or exit meanwhile, as no other
+/// task would be dispatched.
+pub fn enter_maintenance(task_manager: &'static KernelCell<Scheduler>) -> MaintenanceGuard {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        task_manager.borrow(cs_token).borrow_mut().maintenance = true;
+        spinunlock(&TASKMANAGER_LOCK);
+    });
+    MaintenanceGuard { task_manager }
+}
+
+/// Unfreezes the scheduler and dispatches the tasks made ready during the maintenance mode.
+pub fn exit_maintenance(task_manager: &'static KernelCell<Scheduler>) {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        task_manager.borrow(cs_token).borrow_mut().maintenance = false;
+        spinunlock(&TASKMANAGER_LOCK);
+    });
+    schedule(task_manager);
+}
+
+/// Keeps the scheduler frozen while it is alive, see `enter_maintenance`.
+#[must_use]
+pub struct MaintenanceGuard {
+    task_manager: &'static KernelCell<Scheduler>,
+}
+
+impl Drop for MaintenanceGuard {
+    fn drop(&mut self) {
+        exit_maintenance(self.task_manager);
+    }
+}
+
+/// Makes the tasks `first` to `last` a round-robin band. The tasks of a band share the priority of
+/// `last`: the ready tasks of the band take turns, each running for `RR_QUANTUM` ticks before the
+/// next ready task of the band gets the CPU. Bands coexist with the strict priority scheduling, a
+/// band is preempted by the higher priority tasks and preempts the lower priority tasks as a single
+/// task of priority `last` would. The tasks which are not in a band are scheduled as before.
+#[cfg(feature = "round_robin")]
+pub fn set_round_robin_band(
+    task_manager: &'static KernelCell<Scheduler>,
+    first: TaskId,
+    last: TaskId,
+) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let res = task_manager
+            .borrow(cs_token)
+            .borrow_mut()
+            .set_band(first as usize, last as usize);
+        spinunlock(&TASKMANAGER_LOCK);
+        res
+    })
+}
+
+/// Charges the current tick to the quantum of the running task and passes the turn on in its band
+/// once the quantum is used up. Called from SysTick.
+#[cfg(feature = "round_robin")]
+pub(crate) fn round_robin_tick(task_manager: &'static KernelCell<Scheduler>) {
+    critical_section(|cs_token| {
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = &mut task_manager.borrow(cs_token).borrow_mut();
+        if handler.started {
+            handler.round_robin_tick();
+        }
+        spinunlock(&TASKMANAGER_LOCK);
+    })
+}
+
 /// Enable preemptive scheduling
-pub fn enable_preemption(task_manager: &'static Mutex<RefCell<Scheduler>>) {
+pub fn enable_preemption(task_manager: &'static KernelCell<Scheduler>) {
     critical_section(|cs_token| {
         spinlock(&TASKMANAGER_LOCK);
         let handler = &mut task_manager.borrow(cs_token).borrow_mut();
@@ -197,7 +1089,7 @@ pub fn enable_preemption(task_manager: &'static Mutex<RefCell<Scheduler>>) {
 }
 
 /// Disable preemptive scheduling
-pub fn disable_preemption(task_manager: &'static Mutex<RefCell<Scheduler>>) {
+pub fn disable_preemption(task_manager: &'static KernelCell<Scheduler>) {
     critical_section(|cs_token| {
         spinlock(&TASKMANAGER_LOCK);
         let handler = &mut task_manager.borrow(cs_token).borrow_mut();
@@ -1,32 +1,213 @@
 //! Manages the kernel timer.
-use core::cell::RefCell;
 
+use crate::kernel::tasks::{release_periodic, wake_sleepers, TaskManager};
 use crate::priv_execute;
 use crate::utils::arch::is_privileged;
-use crate::utils::arch::{critical_section, svc_call, Mutex, Peripherals, SystClkSource};
+use crate::utils::arch::{
+    critical_section, svc_call, CriticalSection, Peripherals, SystClkSource, SCB, SYST,
+};
+use crate::utils::kernel_cell::KernelCell;
 use crate::KernelError;
 
-static SystemTimer: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+#[cfg(feature = "metrics")]
+use crate::kernel::metrics;
+#[cfg(feature = "multicore")]
+use crate::kernel::tasks::TaskManager_C1;
+
+static SystemTimer: KernelCell<u32> = KernelCell::new(0);
+
+/// The SysTick reload the timer runs with, that is the duration of a tick in cycles minus one.
+static TICK_INTERVAL: KernelCell<u32> = KernelCell::new(0);
+
+/// Counts the cycles of SysTick across reloads of the counter, so that the time stays exact when
+/// the reload is changed in the middle of a tick.
+struct CycleCount {
+    /// The cycles elapsed when the counter was last loaded.
+    load_cycles: u64,
+    /// The value the counter was last loaded with.
+    loaded: u32,
+    /// The cycles elapsed when the tick in progress started.
+    tick_start: u64,
+    /// True if the last wrap of the counter has been counted before the SysTick handler ran.
+    wrap_counted: bool,
+}
+
+impl CycleCount {
+    const fn new() -> Self {
+        Self {
+            load_cycles: 0,
+            loaded: 0,
+            tick_start: 0,
+            wrap_counted: false,
+        }
+    }
+
+    /// Counts a wrap of the counter, which then loaded `reload`.
+    fn wrap(&mut self, reload: u32) {
+        self.load_cycles += self.loaded as u64 + 1;
+        self.loaded = reload;
+        self.tick_start = self.load_cycles;
+    }
+
+    /// Returns the cycles elapsed, `current` is the current value of the counter which must not
+    /// have wrapped since it was loaded.
+    fn now(&self, current: u32) -> u64 {
+        self.load_cycles + (self.loaded - current) as u64
+    }
+}
+
+static CYCLE_COUNT: KernelCell<CycleCount> = KernelCell::new(CycleCount::new());
 
 // TODO: on timer expire raise an event or make a log entry
 
+/// Counts a tick of the kernel timer, wakes up the tasks whose sleep has expired and releases the
+/// periodic tasks which are due.
 pub fn update_time() {
-    critical_section(|cs_token| {
+    let curr_time = critical_section(|cs_token| {
+        let cycle_count = &mut *CYCLE_COUNT.borrow(cs_token).borrow_mut();
+        if !cycle_count.wrap_counted {
+            cycle_count.wrap(SYST::get_reload());
+        }
+        cycle_count.wrap_counted = false;
         let time = &mut *SystemTimer.borrow(cs_token).borrow_mut();
         *time += 1;
-    })
+        *time
+    });
+    wake_sleepers(&TaskManager, curr_time);
+    release_periodic(&TaskManager, curr_time);
+    #[cfg(feature = "multicore")]
+    {
+        wake_sleepers(&TaskManager_C1, curr_time);
+        release_periodic(&TaskManager_C1, curr_time);
+    }
+    #[cfg(feature = "metrics")]
+    metrics::record_tick();
 }
 
 pub fn get_time() -> u32 {
     critical_section(|cs_token| return *SystemTimer.borrow(cs_token).borrow())
 }
 
+/// Returns the time in microseconds, interpolated within the current tick from the SysTick current
+/// value. `core_clock_mhz` is the frequency SysTick counts at, in MHz, and must not be zero. The
+/// time counts the cycles of every tick, hence it stays monotonic when the tick interval changes.
+///
+/// The counter may roll over after the cycle count is read, in which case the SysTick interrupt is
+/// pending but has not counted the wrap yet, as it is masked. The wrap is then counted here and the
+/// current value read again.
+pub fn get_time_us(core_clock_mhz: u32) -> u64 {
+    critical_section(|cs_token| {
+        let cycle_count = &*CYCLE_COUNT.borrow(cs_token).borrow();
+        let mut cycles = cycle_count.now(SYST::get_current());
+        if SCB::is_pendst_pending() && !cycle_count.wrap_counted {
+            let current = SYST::get_current();
+            cycles = cycle_count.load_cycles
+                + cycle_count.loaded as u64
+                + 1
+                + (SYST::get_reload() - current) as u64;
+        }
+        cycles / core_clock_mhz as u64
+    })
+}
+
+/// Returns true if the current tick is a multiple of `n`, so that work can be done every `n`th tick
+/// without keeping a separate divider. Always false for `n == 0`.
+pub fn tick_parity(n: u32) -> bool {
+    n != 0 && get_time() % n == 0
+}
+
 /// Starts the Kernel timer. Timing event manager, logging and task monitor
 /// are heavily dependent on the timer.
 pub fn start_timer(peripherals: &mut Peripherals, tick_interval: u32) {
     let syst = &mut peripherals.SYST;
     syst.set_clock_source(SystClkSource::Core);
     syst.set_reload(tick_interval);
+    syst.clear_current();
     syst.enable_counter();
     syst.enable_interrupt();
+    critical_section(|cs_token| {
+        *TICK_INTERVAL.borrow(cs_token).borrow_mut() = tick_interval;
+        let cycle_count = &mut *CYCLE_COUNT.borrow(cs_token).borrow_mut();
+        // the counter loads the reload on its first cycle, without raising the SysTick interrupt.
+        *cycle_count = CycleCount::new();
+        cycle_count.wrap(tick_interval);
+    });
+
+    #[cfg(feature = "metrics")]
+    {
+        peripherals.DCB.enable_trace();
+        peripherals.DWT.enable_cycle_counter();
+    }
 }
+
+/// Changes the SysTick reload to `reload` while the timer is running, without losing the tick in
+/// progress: the cycles already elapsed in the current tick are carried over, and the current tick
+/// is shortened or lengthened so that it lasts `reload + 1` cycles in total, after which the timer
+/// runs with `reload`. If `reload` cycles or more have already elapsed, the tick is counted right
+/// away. Meant for the tickless idle path, the caller must be privileged.
+pub fn reprogram(reload: u32) -> Result<(), KernelError> {
+    priv_execute!({
+        critical_section(|cs_token| reload_counter(cs_token, reload));
+        Ok(())
+    })
+}
+
+/// Stops SysTick, loads the remainder of the tick in progress as the current value, and restarts it
+/// with `reload`. The counter only stops for the few cycles of the update.
+fn reload_counter(cs_token: &CriticalSection, reload: u32) {
+    let syst = unsafe { &*SYST::ptr() };
+    let cycle_count = &mut *CYCLE_COUNT.borrow(cs_token).borrow_mut();
+    unsafe { syst.csr.modify(|csr| csr & !SYST_CSR_ENABLE) };
+    if SCB::is_pendst_pending() && !cycle_count.wrap_counted {
+        // the counter wrapped but the SysTick handler has not run yet.
+        cycle_count.wrap(SYST::get_reload());
+        cycle_count.wrap_counted = true;
+    }
+    let now = cycle_count.now(SYST::get_current());
+    let elapsed = now - cycle_count.tick_start;
+    let remaining = if elapsed < reload as u64 {
+        reload - elapsed as u32
+    } else {
+        cycle_count.tick_start = now;
+        cycle_count.wrap_counted = true;
+        SCB::set_pendst();
+        reload
+    };
+    cycle_count.load_cycles = now;
+    cycle_count.loaded = remaining;
+    unsafe {
+        // the counter loads RVR on the first cycle after it is enabled with a cleared current
+        // value, RVR can then be changed without affecting the tick in progress.
+        syst.rvr.write(remaining);
+        syst.cvr.write(0);
+        syst.csr.modify(|csr| csr | SYST_CSR_ENABLE);
+        while SYST::get_current() == 0 {}
+        syst.rvr.write(reload);
+    }
+}
+
+/// Changes the tick interval of the running kernel timer to `reload`. Within a single critical
+/// section the counter is disabled, the reload and the current value are written and the counter is
+/// enabled again, the tick in progress is handled as in `reprogram`. The time is kept in ticks,
+/// hence `get_time` keeps counting from where it was and `tick_interval` returns the new interval
+/// from then on. The caller must be privileged.
+///
+/// Sleeps, periodic releases, timer events and deadlines are all expressed in ticks, the pending
+/// ones are not rescaled: they expire after the same number of ticks as before, which now last
+/// `reload + 1` cycles each.
+pub fn set_tick_interval(reload: u32) -> Result<(), KernelError> {
+    priv_execute!({
+        critical_section(|cs_token| {
+            reload_counter(cs_token, reload);
+            *TICK_INTERVAL.borrow(cs_token).borrow_mut() = reload;
+        });
+        Ok(())
+    })
+}
+
+/// Returns the SysTick reload the timer runs with, a tick lasts `tick_interval() + 1` cycles.
+pub fn tick_interval() -> u32 {
+    critical_section(|cs_token| *TICK_INTERVAL.borrow(cs_token).borrow())
+}
+
+const SYST_CSR_ENABLE: u32 = 1;
@@ -27,6 +27,15 @@ extern crate alloc_cortex_m;
 #[allow(non_upper_case_globals)]
 extern crate cortex_m_rt;
 
+#[cfg(all(feature = "edf", feature = "round_robin"))]
+compile_error!("features `edf` and `round_robin` are mutually exclusive, EDF does not use bands");
+
+#[cfg(all(feature = "idle_wfi", feature = "idle_wfe"))]
+compile_error!("features `idle_wfi` and `idle_wfe` are mutually exclusive");
+
+#[cfg(all(feature = "basepri", target_arch = "arm", not(target_feature = "thumb2")))]
+compile_error!("feature `basepri` requires ARMv7-M or later, ARMv6-M has no BASEPRI register");
+
 mod config;
 mod kernel;
 mod system;
@@ -41,55 +50,191 @@ pub mod helpers {
     pub use crate::utils::helpers::TaskMask;
 }
 
+/// Spinlocks synchronizing the cores.
+#[cfg(feature = "multicore")]
+pub mod spinlock {
+    pub use crate::system::spinlock::holder;
+    pub use crate::system::spinlock::set_core_id_source;
+    pub use crate::system::spinlock::CoreId;
+    pub use crate::system::spinlock::TASKMANAGER_LOCK;
+}
+
 /// Kernel routines which assist in Event management.
 #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
 pub mod events {
+    pub use crate::kernel::events::ack;
+    pub use crate::kernel::events::bind_isr;
     pub use crate::kernel::events::disable;
     pub use crate::kernel::events::enable;
     pub use crate::kernel::events::new;
+    pub use crate::kernel::events::raise;
+    pub use crate::system::event::EventId;
 }
 
 /// Kernel timer management.
 #[cfg(feature = "timer")]
 pub mod timer {
+    #[cfg(feature = "embedded_hal")]
+    pub use crate::kernel::delay::KernelDelay;
+    pub use crate::kernel::timer::get_time_us;
+    pub use crate::kernel::timer::reprogram;
+    pub use crate::kernel::timer::set_tick_interval;
     pub use crate::kernel::timer::start_timer;
+    pub use crate::kernel::timer::tick_interval;
+    pub use crate::kernel::timer::tick_parity;
 }
 /// Kernel primitives which assist application development.
 pub mod primitives {
+    pub use crate::system::counting_semaphore::CountingSemaphore;
     pub use crate::system::message::Message;
+    pub use crate::system::message_queue::MessageQueue;
+    pub use crate::system::pi_stack::EmptyPopAction;
+    pub use crate::system::request_reply::{CorrelationId, RequestReply};
     pub use crate::system::resource::PiStackGlobal;
+    #[cfg(feature = "multicore")]
     pub use crate::system::resource::PiStackGlobal_C1;
     pub use crate::system::resource::Resource;
+    pub use crate::system::resource::ResourceGuard;
+    pub use crate::system::resource::try_lock_resource;
+    pub use crate::system::ring_buffer::RingBuffer;
+    pub use crate::system::rw_resource::RwResource;
     pub use crate::system::semaphore::Semaphore;
+    #[cfg(feature = "multicore")]
     pub use crate::system::shared::{Shared, SharedResource};
+    pub use crate::utils::kernel_cell::KernelCell;
+}
+
+/// Registration of resources whose configuration is validated by `start_kernel`.
+pub mod resources {
+    pub use crate::system::resource::{register, validate, ResourceConfig};
 }
 
 /// Kernel routines which assist in Task management.
 pub mod tasks {
     #[cfg(feature = "task_monitor")]
     pub use crate::kernel::task_monitor::set_handler as set_deadline_exceed_handler;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::on_deadline_miss;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::set_deadline_kind;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::set_overrun_handler;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::soft_misses;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::set_wcet;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::time_since_release;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::time_to_deadline;
+    pub use crate::kernel::tasks::allow_privilege;
+    pub use crate::kernel::tasks::block_reason;
+    pub use crate::kernel::tasks::change_priority;
+    #[cfg(feature = "timer")]
+    pub use crate::kernel::tasks::create_periodic_task;
     pub use crate::kernel::tasks::create_task;
+    #[cfg(feature = "timer")]
+    pub use crate::kernel::tasks::create_task_wait;
+    pub use crate::kernel::tasks::destroy_task;
+    pub use crate::kernel::tasks::current_task_name;
     pub use crate::kernel::tasks::disable_preemption;
     pub use crate::kernel::tasks::enable_preemption;
+    pub use crate::kernel::tasks::enter_maintenance;
+    pub use crate::kernel::tasks::exit_maintenance;
+    pub use crate::kernel::tasks::first_task;
     pub use crate::kernel::tasks::get_curr_tid;
+    pub use crate::kernel::tasks::get_preempt_threshold;
     pub use crate::kernel::tasks::init;
+    pub use crate::kernel::tasks::init_no_idle;
+    pub use crate::kernel::tasks::isr_exit;
+    pub use crate::kernel::tasks::join;
+    #[cfg(feature = "timer")]
+    pub use crate::kernel::tasks::missed_periods;
+    pub use crate::kernel::tasks::pending_preemption;
+    pub use crate::kernel::tasks::ready_blocked_snapshot;
+    pub use crate::kernel::tasks::reap;
     pub use crate::kernel::tasks::release;
+    pub use crate::kernel::tasks::set_idle_hook;
+    pub use crate::kernel::tasks::set_pre_sleep_hook;
+    pub use crate::kernel::tasks::set_preempt_threshold;
+    pub use crate::kernel::tasks::set_reaper;
+    pub use crate::kernel::tasks::set_ready_set;
+    #[cfg(feature = "round_robin")]
+    pub use crate::kernel::tasks::set_round_robin_band;
+    pub use crate::kernel::tasks::set_task_name;
+    #[cfg(feature = "stack_check")]
+    pub use crate::kernel::tasks::set_stack_corruption_handler;
+    #[cfg(feature = "self_test")]
+    pub use crate::kernel::self_test::self_test;
+    pub use crate::kernel::tasks::set_successors;
+    pub use crate::kernel::tasks::set_svc_fallback;
+    #[cfg(feature = "stack_check")]
+    pub use crate::kernel::tasks::stack_usage;
+    #[cfg(feature = "timer")]
+    pub use crate::kernel::tasks::release_rate_limited;
+    #[cfg(feature = "system_logger")]
+    pub use crate::kernel::tasks::release_traced;
+    #[cfg(feature = "timer")]
+    pub use crate::kernel::tasks::sleep;
     pub use crate::kernel::tasks::start_kernel;
     pub use crate::kernel::tasks::task_exit;
+    pub use crate::kernel::tasks::task_name;
+    pub use crate::kernel::tasks::transaction;
+    pub use crate::kernel::tasks::yield_task;
+    pub use crate::kernel::tasks::MaintenanceGuard;
+    pub use crate::kernel::tasks::TaskHandle;
     pub use crate::kernel::tasks::TaskManager;
+    #[cfg(feature = "multicore")]
     pub use crate::kernel::tasks::TaskManager_C1;
+    pub use crate::kernel::tasks::Transaction;
+    pub use crate::utils::arch::with_privilege;
+    pub use crate::utils::arch::SleepDepth;
     // TODO: move to another namespace or remove
     pub use crate::system::scheduler::Scheduler;
+    pub use crate::system::scheduler::BlockReason;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::system::task_monitor::DeadlineKind;
+}
+
+/// Export of the kernel state for host side monitoring.
+pub mod diagnostics {
+    pub use crate::kernel::diagnostics::encode_state;
+    pub use crate::kernel::diagnostics::{FRAME_LEN, FRAME_VERSION};
+    pub use crate::system::resource::current_system_ceiling;
+    pub use crate::system::resource::is_resource_locked;
+}
+
+/// Kernel runtime metrics.
+#[cfg(any(feature = "metrics", feature = "task_monitor"))]
+pub mod metrics {
+    #[cfg(feature = "metrics")]
+    pub use crate::kernel::metrics::dispatch_count;
+    #[cfg(feature = "metrics")]
+    pub use crate::kernel::metrics::lost_ticks;
+    #[cfg(feature = "metrics")]
+    pub use crate::kernel::metrics::max_hold_time;
+    #[cfg(feature = "metrics")]
+    pub use crate::kernel::metrics::max_inversion;
+    #[cfg(feature = "metrics")]
+    pub use crate::kernel::metrics::reset_dispatch_counts;
+    #[cfg(feature = "task_monitor")]
+    pub use crate::kernel::task_monitor::runtime_snapshot;
 }
 
 #[cfg(feature = "system_logger")]
 /// Kernel routines which handle log management.
 pub mod logging {
+    pub use crate::kernel::logging::claim;
+    pub use crate::kernel::logging::clear;
     pub use crate::kernel::logging::process;
+    pub use crate::kernel::logging::process_n;
+    pub use crate::kernel::logging::release_claim;
     pub use crate::kernel::logging::set_all;
     pub use crate::kernel::logging::set_block_tasks;
+    pub use crate::kernel::logging::set_idle_sink;
     pub use crate::kernel::logging::set_message_broadcast;
     pub use crate::kernel::logging::set_message_recieve;
+    pub use crate::kernel::logging::set_priority_inheritance;
     pub use crate::kernel::logging::set_release;
     pub use crate::kernel::logging::set_resource_lock;
     pub use crate::kernel::logging::set_resource_unlock;
@@ -98,6 +243,7 @@ pub mod logging {
     pub use crate::kernel::logging::set_task_exit;
     pub use crate::kernel::logging::set_timer_event;
     pub use crate::kernel::logging::set_unblock_tasks;
+    pub use crate::kernel::logging::LogToken;
     pub use crate::system::system_logger::LogEvent;
 }
 
@@ -2,8 +2,10 @@
 
 /// The tasks must be looping infinitely and call `task_exit` whenever a particular task is done.
 /// This makes it complicated to create tasks and also might introduce undefined behavior if task_exit is not called.
-/// The `spawn` macro makes it easier to define tasks. It also defines a static variable of type TaskId,
-/// which corresponds to the task created.
+/// The `spawn` macro makes it easier to define tasks. It evaluates to the `TaskHandle` of the task
+/// created.
+/// If the task is prefixed with a name, the name is registered for the task and can be looked up
+/// with `task_name`.
 ///
 /// ## Examples
 ///
@@ -12,12 +14,57 @@
 /// spawn!(task2, 2, stack1, shared, params, {
 ///     hprintln!("{}", params);
 /// });
-/// spawn!(task3, 3, stack2, {
+/// let task3 = spawn!(task3, 3, stack2, {
 ///     hprintln!("Hello!");
 /// });
+/// task3.release();
+/// spawn!(logger => &TaskManager, 4, stack3, {
+///     hprintln!("{}", current_task_name(&TaskManager));
+/// });
 /// ```
 #[macro_export]
 macro_rules! spawn {
+    ($task_name: ident => $tm: expr, $priority: expr, $stack: expr, $handler_fn: block) => {
+        $crate::try_spawn!($task_name => $tm, $priority, $stack, $handler_fn).unwrap()
+    };
+    ($task_name: ident => $tm: expr, $priority: expr, $deadline: expr, $stack: expr, $handler_fn: block) => {
+        $crate::try_spawn!($task_name => $tm, $priority, $deadline, $stack, $handler_fn).unwrap()
+    };
+    ($tm: expr, $priority: expr, $stack: expr, $handler_fn: block) => {
+        $crate::try_spawn!($tm, $priority, $stack, $handler_fn).unwrap()
+    };
+    ($tm: expr, $priority: expr, $deadline: expr, $stack: expr, $handler_fn: block) => {
+        $crate::try_spawn!($tm, $priority, $deadline, $stack, $handler_fn).unwrap()
+    };
+}
+
+/// Same as `spawn!`, but evaluates to the `Result` of creating the task instead of panicking, so
+/// that the caller can handle a failure, e.g. a stack too small or a priority already taken.
+///
+/// ## Example
+///
+/// ```rust
+/// match try_spawn!(&TaskManager, 2, stack1, {
+///     hprintln!("Hello!");
+/// }) {
+///     Ok(task2) => task2.release(),
+///     Err(err) => hprintln!("task2 not created: {:?}", err),
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_spawn {
+    ($task_name: ident => $tm: expr, $priority: expr, $stack: expr, $handler_fn: block) => {
+        $crate::try_spawn!($tm, $priority, $stack, $handler_fn).and_then(|handle| {
+            $crate::tasks::set_task_name($tm, $priority, stringify!($task_name))?;
+            Ok(handle)
+        })
+    };
+    ($task_name: ident => $tm: expr, $priority: expr, $deadline: expr, $stack: expr, $handler_fn: block) => {
+        $crate::try_spawn!($tm, $priority, $deadline, $stack, $handler_fn).and_then(|handle| {
+            $crate::tasks::set_task_name($tm, $priority, stringify!($task_name))?;
+            Ok(handle)
+        })
+    };
     ($tm: expr, $priority: expr, $stack: expr, $handler_fn: block) => {
         create_task(
             $tm,
@@ -26,7 +73,7 @@ macro_rules! spawn {
             || loop {
                 $handler_fn
                 task_exit($tm);
-        }).unwrap();
+        })
     };
     ($tm: expr, $priority: expr, $deadline: expr, $stack: expr, $handler_fn: block) => {
         create_task(
@@ -37,7 +84,32 @@ macro_rules! spawn {
             || loop {
                 $handler_fn
                 task_exit($tm);
-        }).unwrap();
+        })
+    };
+}
+
+/// Defines the interrupt handler `$irq` which can release tasks of the task manager `$tm`. On exit
+/// of the handler, a context switch is pended if a task which should preempt the running task became
+/// ready, so that the handler body only has to release the tasks. The `interrupt` attribute of the
+/// device crate must be in scope.
+///
+/// ## Example
+///
+/// ```rust
+/// use stm32f4::stm32f407::interrupt;
+///
+/// kernel_interrupt!(EXTI0, &TaskManager, {
+///     release(&TaskManager, TaskMask::<1>::generate([2]));
+/// });
+/// ```
+#[macro_export]
+macro_rules! kernel_interrupt {
+    ($irq: ident, $tm: expr, $body: block) => {
+        #[interrupt]
+        fn $irq() {
+            $body
+            $crate::tasks::isr_exit($tm);
+        }
     };
 }
 
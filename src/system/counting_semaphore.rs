@@ -0,0 +1,131 @@
+//! # Counting semaphore definition
+//!
+use crate::kernel::tasks::{block_tasks, get_curr_tid, schedule, unblock_tasks};
+use crate::system::scheduler::{BlockReason, BooleanVector, Scheduler};
+use crate::utils::arch::critical_section;
+use crate::utils::helpers::get_msb;
+use crate::utils::kernel_cell::KernelCell;
+use core::cell::RefCell;
+
+#[cfg(feature = "system_logger")]
+use {crate::kernel::logging, crate::system::system_logger::LogEventType};
+
+/// A semaphore holding a count of units, tasks waiting for a unit are blocked until one is posted.
+pub struct CountingSemaphore {
+    count: RefCell<u32>,
+    /// A boolean vector of the tasks blocked in `wait`.
+    waiters: RefCell<BooleanVector>,
+    /// Number of resets, lets the waiters tell a reset from a post.
+    resets: RefCell<u32>,
+
+    /// A reference to access the kernel functions
+    task_manager: &'static KernelCell<Scheduler>,
+}
+
+impl CountingSemaphore {
+    /// Initializes a new counting semaphore holding `count` units.
+    pub const fn new(task_manager: &'static KernelCell<Scheduler>, count: u32) -> Self {
+        Self {
+            count: RefCell::new(count),
+            waiters: RefCell::new(0),
+            resets: RefCell::new(0),
+            task_manager,
+        }
+    }
+
+    /// Takes a unit, the currently running task is blocked until a unit is posted if there is none.
+    /// Returns false if the wait was ended by `reset`, in which case no unit was taken.
+    pub fn wait(&'static self) -> bool {
+        let resets = critical_section(|_| *self.resets.borrow());
+        while !self.take(true) {
+            schedule(self.task_manager);
+            if critical_section(|_| *self.resets.borrow()) != resets {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Takes a unit if there is one, returns false without blocking if there is none.
+    pub fn try_wait(&'static self) -> bool {
+        self.take(false)
+    }
+
+    /// Takes a unit, if there is none and `block` is true the currently running task is added to
+    /// the waiters and blocked.
+    fn take(&self, block: bool) -> bool {
+        critical_section(|_| {
+            let count = &mut *self.count.borrow_mut();
+            if *count > 0 {
+                *count -= 1;
+                return true;
+            }
+            if block {
+                let curr_tid_mask = 1 << get_curr_tid(self.task_manager);
+                *self.waiters.borrow_mut() |= curr_tid_mask;
+                block_tasks(self.task_manager, curr_tid_mask, BlockReason::EventWait);
+            }
+            false
+        })
+    }
+
+    /// Posts a unit and unblocks the highest priority waiter, if any.
+    pub fn post(&'static self) {
+        let released = critical_section(|_| {
+            let count = &mut *self.count.borrow_mut();
+            *count += 1;
+            let waiters = &mut *self.waiters.borrow_mut();
+            let released = match get_msb(*waiters) {
+                Some(tid) => 1 << tid,
+                None => 0,
+            };
+            *waiters &= !released;
+            #[cfg(feature = "system_logger")]
+            {
+                if logging::get_semaphore_signal() {
+                    logging::report(LogEventType::SemaphorePost(*count, released));
+                }
+            }
+            released
+        });
+        if released != 0 {
+            unblock_tasks(self.task_manager, released, BlockReason::EventWait);
+            schedule(self.task_manager);
+        }
+    }
+
+    /// Clears the count to zero and unblocks all the waiters at once, their `wait` returns false.
+    /// Returns the number of tasks unblocked.
+    pub fn reset(&'static self) -> u32 {
+        let released = critical_section(|_| {
+            *self.count.borrow_mut() = 0;
+            let resets = &mut *self.resets.borrow_mut();
+            *resets = resets.wrapping_add(1);
+            let released = self.waiters.replace(0);
+            unblock_tasks(self.task_manager, released, BlockReason::EventWait);
+            #[cfg(feature = "system_logger")]
+            {
+                if logging::get_semaphore_reset() {
+                    logging::report(LogEventType::SemaphoreReset(get_curr_tid(self.task_manager)));
+                }
+            }
+            released
+        });
+        if released != 0 {
+            schedule(self.task_manager);
+        }
+        released.count_ones()
+    }
+
+    /// Returns the number of units held by the semaphore.
+    pub fn count(&self) -> u32 {
+        critical_section(|_| *self.count.borrow())
+    }
+
+    /// Returns the boolean vector of tasks waiting for a unit.
+    pub fn waiters(&self) -> BooleanVector {
+        critical_section(|_| *self.waiters.borrow())
+    }
+}
+
+unsafe impl Sync for CountingSemaphore {}
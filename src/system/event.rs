@@ -3,12 +3,16 @@
 //! Defines Data-structures to manage events.
 
 use crate::config::EVENT_COUNT;
+use crate::system::scheduler::{Scheduler, TaskId};
 use crate::utils::errors::KernelError;
+use crate::utils::kernel_cell::KernelCell;
 
 #[cfg(feature = "system_logger")]
 use {crate::kernel::logging, crate::system::system_logger::LogEventType};
 
-pub type EventId = usize;
+/// Identifies an event in the event table, returned when the event is created.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EventId(usize);
 
 /// Event Descriptor
 #[derive(Clone, Copy)]
@@ -19,6 +23,10 @@ pub struct Event {
     /// This is the frequency (of time unit in which it belongs to) in which the Event should run.
     threshold: u32,
     handler: fn() -> (),
+    /// The task released when the event is raised from an ISR.
+    bound_task: Option<(&'static KernelCell<Scheduler>, TaskId)>,
+    /// True if the event was raised and the bound task has not acknowledged it yet.
+    pending_ack: bool,
 }
 
 impl Event {
@@ -66,7 +74,7 @@ impl EventTable {
 
     /// Enables an Event.
     pub fn enable(&mut self, event_id: EventId) -> Result<(), KernelError> {
-        let event = &mut self.events[event_id]
+        let event = &mut self.events[event_id.0]
             .as_mut()
             .ok_or(KernelError::NotFound)?;
         event.is_enabled = true;
@@ -75,7 +83,7 @@ impl EventTable {
 
     /// Disables an Event.
     pub fn disable(&mut self, event_id: EventId) -> Result<(), KernelError> {
-        let event = &mut self.events[event_id]
+        let event = &mut self.events[event_id.0]
             .as_mut()
             .ok_or(KernelError::NotFound)?;
         event.is_enabled = false;
@@ -89,17 +97,68 @@ impl EventTable {
         threshold: u32,
         handler: fn() -> (),
     ) -> Result<EventId, KernelError> {
-        let id = self.curr;
-        if id >= self.events.len() {
+        if self.curr >= self.events.len() {
             return Err(KernelError::LimitExceeded);
         }
-        self.events[id] = Some(Event {
+        let id = EventId(self.curr);
+        self.events[id.0] = Some(Event {
             event_id: id,
             is_enabled,
             threshold,
             handler,
+            bound_task: None,
+            pending_ack: false,
         });
         self.curr += 1;
         return Ok(id);
     }
+
+    /// Binds the task `tid` to the event, the task is released whenever the event is raised.
+    pub fn bind(
+        &mut self,
+        event_id: EventId,
+        task_manager: &'static KernelCell<Scheduler>,
+        tid: TaskId,
+    ) -> Result<(), KernelError> {
+        let event = &mut self.events[event_id.0]
+            .as_mut()
+            .ok_or(KernelError::NotFound)?;
+        event.bound_task = Some((task_manager, tid));
+        event.pending_ack = false;
+        Ok(())
+    }
+
+    /// Marks the event as pending acknowledgment and returns the bound task which has to be released.
+    /// Returns `None` if the previous raise has not been acknowledged yet, so that bursts are coalesced.
+    pub fn raise(
+        &mut self,
+        event_id: EventId,
+    ) -> Result<Option<(&'static KernelCell<Scheduler>, TaskId)>, KernelError> {
+        let event = &mut self.events[event_id.0]
+            .as_mut()
+            .ok_or(KernelError::NotFound)?;
+        let bound_task = event.bound_task.ok_or(KernelError::NotFound)?;
+        if event.pending_ack {
+            return Ok(None);
+        }
+        event.pending_ack = true;
+        Ok(Some(bound_task))
+    }
+
+    /// Returns the number of raised events which are not acknowledged yet.
+    pub fn pending_count(&self) -> usize {
+        self.events[..self.curr]
+            .iter()
+            .filter(|event| event.map_or(false, |event| event.pending_ack))
+            .count()
+    }
+
+    /// Acknowledges the event, the next raise releases the bound task again.
+    pub fn ack(&mut self, event_id: EventId) -> Result<(), KernelError> {
+        let event = &mut self.events[event_id.0]
+            .as_mut()
+            .ok_or(KernelError::NotFound)?;
+        event.pending_ack = false;
+        Ok(())
+    }
 }
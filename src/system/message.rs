@@ -7,7 +7,8 @@ use crate::system::scheduler::BooleanVector;
 use crate::system::scheduler::Scheduler;
 use crate::system::semaphore::Semaphore;
 use crate::tasks::get_curr_tid;
-use crate::utils::arch::{critical_section, Mutex};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
 
 #[cfg(feature = "system_logger")]
 use {crate::kernel::logging, crate::system::system_logger::LogEventType};
@@ -17,12 +18,17 @@ pub struct Message<T: Sized + Clone> {
     value: RefCell<T>,
     pub receivers: BooleanVector,
     semaphore: Semaphore,
+    /// A boolean vector of the receivers which have not yet received the last broadcast.
+    unacked: RefCell<BooleanVector>,
+
+    /// A reference to access the kernel functions
+    task_manager: &'static KernelCell<Scheduler>,
 }
 
 impl<T: Sized + Clone> Message<T> {
     /// Create and initialize new message object
     pub const fn new(
-        task_manager: &'static Mutex<RefCell<Scheduler>>,
+        task_manager: &'static KernelCell<Scheduler>,
         tasks_mask: BooleanVector,
         receivers_mask: BooleanVector,
         value: T,
@@ -31,6 +37,8 @@ impl<T: Sized + Clone> Message<T> {
             value: RefCell::new(value),
             receivers: receivers_mask,
             semaphore: Semaphore::new(task_manager, tasks_mask),
+            unacked: RefCell::new(0),
+            task_manager,
         }
     }
 
@@ -40,6 +48,7 @@ impl<T: Sized + Clone> Message<T> {
             if let Some(msg) = msg {
                 self.value.replace(msg);
             }
+            self.unacked.replace(self.receivers);
             self.semaphore.signal_and_release(self.receivers);
             #[cfg(feature = "system_logger")]
             {
@@ -50,10 +59,33 @@ impl<T: Sized + Clone> Message<T> {
         })
     }
 
+    /// Returns the boolean vector of tasks which receive the message on a broadcast.
+    pub fn subscribers(&self) -> BooleanVector {
+        self.receivers
+    }
+
+    /// Returns the boolean vector of tasks released by a broadcast of the message.
+    pub fn targets(&self) -> BooleanVector {
+        self.semaphore.targets()
+    }
+
+    /// Returns the boolean vector of receivers which have a message pending, that is, which have
+    /// not received the last broadcast yet.
+    pub fn waiters(&self) -> BooleanVector {
+        self.semaphore.waiters()
+    }
+
+    /// Returns true once every receiver has received the last broadcast, from then on the message
+    /// can be broadcast again without any receiver missing a value.
+    pub fn broadcast_delivered(&self) -> bool {
+        critical_section(|_| *self.unacked.borrow() == 0)
+    }
+
     /// Get a copy of the messsage on recieving a message
     pub fn receive(&'static self) -> Option<T> {
         critical_section(|_| match self.semaphore.test_and_reset() {
             Ok(res) if res == true => {
+                *self.unacked.borrow_mut() &= !(1 << get_curr_tid(self.task_manager));
                 #[cfg(feature = "system_logger")]
                 {
                     if logging::get_message_recieve() {
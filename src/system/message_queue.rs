@@ -0,0 +1,122 @@
+//! Message queue primitive
+//!
+
+use core::cell::RefCell;
+
+use crate::config::{MAX_MSG_DEPTH, MAX_TASKS};
+use crate::kernel::tasks::{block_tasks, get_curr_tid, release, schedule, unblock_tasks};
+use crate::system::ring_buffer::RingBuffer;
+use crate::system::scheduler::{BlockReason, BooleanVector, Scheduler};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
+use crate::KernelError;
+
+#[cfg(feature = "system_logger")]
+use {crate::kernel::logging, crate::system::system_logger::LogEventType};
+
+/// Holds the messages sent to the receivers until they are received, unlike `Message` a receiver
+/// gets every message sent, in order, as long as it keeps up within `MAX_MSG_DEPTH` messages.
+pub struct MessageQueue<T: Copy> {
+    /// The messages sent to each receiver and not yet received.
+    rings: RefCell<[RingBuffer<T, MAX_MSG_DEPTH>; MAX_TASKS]>,
+    pub receivers: BooleanVector,
+    /// A boolean vector of the tasks released on a send.
+    tasks: BooleanVector,
+    /// A boolean vector of the receivers blocked until a message arrives.
+    waiters: RefCell<BooleanVector>,
+
+    /// A reference to access the kernel functions
+    task_manager: &'static KernelCell<Scheduler>,
+}
+
+impl<T: Copy> MessageQueue<T> {
+    /// Create and initialize new message queue object
+    pub const fn new(
+        task_manager: &'static KernelCell<Scheduler>,
+        tasks_mask: BooleanVector,
+        receivers_mask: BooleanVector,
+    ) -> Self {
+        Self {
+            rings: RefCell::new([RingBuffer::new(); MAX_TASKS]),
+            receivers: receivers_mask,
+            tasks: tasks_mask,
+            waiters: RefCell::new(0),
+            task_manager,
+        }
+    }
+
+    /// Queues `msg` for every receiver and releases the tasks of the queue. If the queue of any
+    /// receiver is full, the message is not queued for any of them and `BufferFull` is returned.
+    pub fn send(&'static self, msg: T) -> Result<(), KernelError> {
+        let waiters = critical_section(|_| {
+            let rings = &mut *self.rings.borrow_mut();
+            let is_full = (0..MAX_TASKS)
+                .any(|tid| self.receivers & (1 << tid) > 0 && rings[tid].is_full());
+            if is_full {
+                return Err(KernelError::BufferFull);
+            }
+            for tid in 0..MAX_TASKS {
+                if self.receivers & (1 << tid) > 0 {
+                    let _ = rings[tid].push(msg);
+                }
+            }
+            #[cfg(feature = "system_logger")]
+            {
+                if logging::get_message_broadcast() {
+                    logging::report(LogEventType::MessageBroadcast(self.receivers));
+                }
+            }
+            Ok(self.waiters.replace(0))
+        })?;
+        unblock_tasks(self.task_manager, waiters, BlockReason::EventWait);
+        release(self.task_manager, self.tasks);
+        schedule(self.task_manager);
+        Ok(())
+    }
+
+    /// Returns the oldest message queued for the currently running task, `None` if there is none.
+    pub fn receive(&'static self) -> Option<T> {
+        self.pop(false)
+    }
+
+    /// Returns the oldest message queued for the currently running task, the task is blocked until
+    /// a message arrives if there is none.
+    pub fn receive_blocking(&'static self) -> T {
+        loop {
+            if let Some(msg) = self.pop(true) {
+                return msg;
+            }
+            schedule(self.task_manager);
+        }
+    }
+
+    /// Pops the oldest message of the currently running task, if there is none and `block` is true
+    /// the task is added to the waiters and blocked.
+    fn pop(&self, block: bool) -> Option<T> {
+        critical_section(|_| {
+            let curr_tid = get_curr_tid(self.task_manager);
+            if self.receivers & (1 << curr_tid) == 0 {
+                return None;
+            }
+            let msg = self.rings.borrow_mut()[curr_tid as usize].pop();
+            match msg {
+                Some(_) => {
+                    #[cfg(feature = "system_logger")]
+                    {
+                        if logging::get_message_recieve() {
+                            logging::report(LogEventType::MessageRecieve(curr_tid));
+                        }
+                    }
+                }
+                None if block => {
+                    *self.waiters.borrow_mut() |= 1 << curr_tid;
+                    block_tasks(self.task_manager, 1 << curr_tid, BlockReason::EventWait);
+                }
+                None => {}
+            }
+            msg
+        })
+    }
+}
+
+unsafe impl<T: Copy> Sync for MessageQueue<T> {}
@@ -0,0 +1,37 @@
+//! # Metrics
+//!
+//! Defines Data-structures which hold the runtime metrics of the Kernel.
+
+pub struct Metrics {
+    /// Number of SysTick interrupts that were missed.
+    lost_ticks: u32,
+    /// The cycle count at the previous SysTick interrupt.
+    last_tick_cycles: Option<u32>,
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            lost_ticks: 0,
+            last_tick_cycles: None,
+        }
+    }
+
+    /// Compares the cycles elapsed since the previous tick against the cycles per tick, if more
+    /// than one tick period has elapsed then the extra ticks were lost.
+    pub fn record_tick(&mut self, curr_cycles: u32, tick_cycles: u32) {
+        if let Some(last_tick_cycles) = self.last_tick_cycles {
+            let elapsed = curr_cycles.wrapping_sub(last_tick_cycles);
+            // rounded to the nearest tick so that interrupt latency jitter is not counted.
+            let elapsed_ticks = (elapsed + tick_cycles / 2) / tick_cycles;
+            if elapsed_ticks > 1 {
+                self.lost_ticks += elapsed_ticks - 1;
+            }
+        }
+        self.last_tick_cycles = Some(curr_cycles);
+    }
+
+    pub fn lost_ticks(&self) -> u32 {
+        self.lost_ticks
+    }
+}
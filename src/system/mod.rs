@@ -1,10 +1,16 @@
 //! Kernel Data-structures. `Private`
 
+pub mod counting_semaphore;
 pub mod message;
-mod pi_stack;
+pub mod message_queue;
+pub mod pi_stack;
+pub mod request_reply;
 pub mod resource;
+pub mod ring_buffer;
+pub mod rw_resource;
 pub mod scheduler;
 pub mod semaphore;
+#[cfg(feature = "multicore")]
 pub mod shared;
 pub mod spinlock;
 
@@ -16,3 +22,6 @@ pub mod system_logger;
 
 #[cfg(feature = "task_monitor")]
 pub mod task_monitor;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
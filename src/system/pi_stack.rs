@@ -3,18 +3,30 @@
 //! The Resource manager handles the details of which processes have access to the which resource
 //! and implements the locking and unlocking mechanism.
 
-use crate::config::MAX_RESOURCES;
+use crate::config::{EMPTY_POP_ACTION, MAX_LOCK_NESTING};
 use crate::system::scheduler::TaskId;
 use crate::KernelError;
 
 const PI: i32 = -1;
 
+/// What `pop_stack` does when the stack is empty, i.e. when a resource that was never locked is
+/// unlocked. Selected by `EMPTY_POP_ACTION` in the config.
+pub enum EmptyPopAction {
+    /// Return the error to the caller.
+    ReturnError,
+    /// Fail a `debug_assert!`, returns the error in release builds.
+    Assert,
+    /// Call the handler, then return the error.
+    Handler(fn()),
+}
+
 pub struct PiStack {
     /// Points the top of the `pi_stack`.
     top: usize,
-    /// This stack is used for locking and unlocking of resources.
+    /// This stack is used for locking and unlocking of resources. The bottom entry holds the
+    /// ceiling of the empty stack, hence `MAX_LOCK_NESTING` locks can be nested on top of it.
     // TODO: Why is this i32 and not u32??
-    pi_stack: [i32; MAX_RESOURCES],
+    pi_stack: [i32; MAX_LOCK_NESTING + 1],
     /// Hold the ceiling of the resource with the highest ceiling amongst the currently locked resources.
     pub system_ceiling: i32,
 }
@@ -23,14 +35,29 @@ impl PiStack {
     pub const fn new() -> Self {
         Self {
             top: 0,
-            pi_stack: [PI; MAX_RESOURCES],
+            pi_stack: [PI; MAX_LOCK_NESTING + 1],
             system_ceiling: PI,
         }
     }
 
-    /// Pops the stack top and assigns the `system_ceiling` to the new stack top.
-    pub fn pop_stack(&mut self) -> Result<(), KernelError> {
+    /// Pops the stack top and assigns the `system_ceiling` to the new stack top. `resource` and
+    /// `ceiling` identify the resource being unlocked, they are reported by the error of an empty
+    /// stack.
+    #[cfg_attr(not(feature = "rich_errors"), allow(unused_variables))]
+    pub fn pop_stack(&mut self, resource: usize, ceiling: TaskId) -> Result<(), KernelError> {
         if self.top == 0 {
+            match EMPTY_POP_ACTION {
+                EmptyPopAction::ReturnError => {}
+                EmptyPopAction::Assert => debug_assert!(false, "pi_stack popped while empty"),
+                EmptyPopAction::Handler(handler) => handler(),
+            }
+            #[cfg(feature = "rich_errors")]
+            return Err(KernelError::PiStackEmpty {
+                resource,
+                ceiling,
+                system_ceiling: self.system_ceiling,
+            });
+            #[cfg(not(feature = "rich_errors"))]
             return Err(KernelError::Empty);
         }
         self.top -= 1;
@@ -38,18 +65,35 @@ impl PiStack {
         Ok(())
     }
 
-    pub fn top(&self) -> u32 {
-        // XXX does this check bounds
-        // do we need a bounds check?
-        self.pi_stack[self.top] as u32
+    /// Returns the number of ceilings pushed onto the pi_stack.
+    pub fn depth(&self) -> usize {
+        self.top
     }
 
-    /// Pushes the passed ceiling onto the pi_stack.
-    pub fn push_stack(&mut self, ceiling: TaskId) -> Result<(), KernelError> {
-        self.top += 1;
-        if self.top >= MAX_RESOURCES {
+    /// Returns the ceiling on top of the pi_stack, `Empty` if no ceiling has been pushed.
+    pub fn top(&self) -> Result<u32, KernelError> {
+        if self.top == 0 {
+            return Err(KernelError::Empty);
+        }
+        Ok(self.pi_stack[self.top] as u32)
+    }
+
+    /// Pushes the ceiling of the resource `resource` onto the pi_stack. The stack is left untouched
+    /// if it is full.
+    #[cfg_attr(not(feature = "rich_errors"), allow(unused_variables))]
+    pub fn push_stack(&mut self, resource: usize, ceiling: TaskId) -> Result<(), KernelError> {
+        if self.top >= MAX_LOCK_NESTING {
+            #[cfg(feature = "rich_errors")]
+            return Err(KernelError::PiStackFull {
+                resource,
+                ceiling,
+                top: self.top,
+                system_ceiling: self.system_ceiling,
+            });
+            #[cfg(not(feature = "rich_errors"))]
             return Err(KernelError::LimitExceeded);
         }
+        self.top += 1;
         self.pi_stack[self.top] = ceiling as i32;
         self.system_ceiling = ceiling as i32;
         Ok(())
@@ -0,0 +1,142 @@
+//! # Request/reply primitive
+//!
+//! Builds request/reply round trips on top of task release and blocking. Every request gets a
+//! correlation id, which matches the reply to the request even when several requests are
+//! outstanding at once.
+
+use core::cell::RefCell;
+
+use crate::config::MAX_PENDING_REQUESTS;
+use crate::kernel::tasks::{block_tasks, get_curr_tid, release, schedule, unblock_tasks};
+use crate::system::scheduler::{BlockReason, BooleanVector, Scheduler, TaskId};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
+use crate::KernelError;
+
+/// Identifies a request and the reply to it.
+pub type CorrelationId = u32;
+
+#[derive(Clone, Copy)]
+struct Pending<Q: Copy, R: Copy> {
+    id: CorrelationId,
+    /// The task waiting for the reply.
+    requester: TaskId,
+    /// The request, `None` once a server has taken it.
+    request: Option<Q>,
+    reply: Option<R>,
+}
+
+/// Carries requests of type `Q` from requesting tasks to the server tasks and replies of type `R`
+/// back. At most `MAX_PENDING_REQUESTS` requests can be outstanding.
+pub struct RequestReply<Q: Copy, R: Copy> {
+    /// A boolean vector of the tasks released on a request.
+    servers: BooleanVector,
+    pending: RefCell<[Option<Pending<Q, R>>; MAX_PENDING_REQUESTS]>,
+    next_id: RefCell<CorrelationId>,
+
+    /// A reference to access the kernel functions
+    task_manager: &'static KernelCell<Scheduler>,
+}
+
+impl<Q: Copy, R: Copy> RequestReply<Q, R> {
+    /// Create and initialize new request/reply object, the tasks in `servers_mask` serve the
+    /// requests.
+    pub const fn new(
+        task_manager: &'static KernelCell<Scheduler>,
+        servers_mask: BooleanVector,
+    ) -> Self {
+        Self {
+            servers: servers_mask,
+            pending: RefCell::new([None; MAX_PENDING_REQUESTS]),
+            next_id: RefCell::new(0),
+            task_manager,
+        }
+    }
+
+    /// Queues `payload` on behalf of the currently running task and releases the server tasks.
+    /// Returns `LimitExceeded` if `MAX_PENDING_REQUESTS` requests are already outstanding.
+    pub fn request(&'static self, payload: Q) -> Result<CorrelationId, KernelError> {
+        let requester = get_curr_tid(self.task_manager);
+        let id = critical_section(|_| {
+            let pending = &mut *self.pending.borrow_mut();
+            let slot = pending
+                .iter()
+                .position(|p| p.is_none())
+                .ok_or(KernelError::LimitExceeded)?;
+            let next_id = &mut *self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id = next_id.wrapping_add(1);
+            pending[slot] = Some(Pending {
+                id,
+                requester,
+                request: Some(payload),
+                reply: None,
+            });
+            Ok(id)
+        })?;
+        release(self.task_manager, self.servers);
+        Ok(id)
+    }
+
+    /// Takes a request which has not been served yet, to be called by the server tasks.
+    pub fn next_request(&'static self) -> Option<(CorrelationId, Q)> {
+        critical_section(|_| {
+            let pending = &mut *self.pending.borrow_mut();
+            pending
+                .iter_mut()
+                .flatten()
+                .find(|p| p.request.is_some())
+                .map(|p| (p.id, p.request.take().unwrap()))
+        })
+    }
+
+    /// Replies to the request `id` and unblocks the requester if it is waiting for the reply.
+    /// Returns `NotFound` if the request is not outstanding or has already been replied to.
+    pub fn reply(&'static self, id: CorrelationId, payload: R) -> Result<(), KernelError> {
+        let requester = critical_section(|_| {
+            let pending = &mut *self.pending.borrow_mut();
+            let p = pending
+                .iter_mut()
+                .flatten()
+                .find(|p| p.id == id && p.reply.is_none())
+                .ok_or(KernelError::NotFound)?;
+            p.reply = Some(payload);
+            Ok(p.requester)
+        })?;
+        unblock_tasks(self.task_manager, 1 << requester, BlockReason::EventWait);
+        schedule(self.task_manager);
+        Ok(())
+    }
+
+    /// Blocks the currently running task until the request `id` is replied to and returns the
+    /// reply, which frees the request. Returns `NotFound` if the request is not outstanding.
+    pub fn wait_reply(&'static self, id: CorrelationId) -> Result<R, KernelError> {
+        loop {
+            let reply = critical_section(|_| {
+                let pending = &mut *self.pending.borrow_mut();
+                let slot = pending
+                    .iter()
+                    .position(|p| p.map_or(false, |p| p.id == id))
+                    .ok_or(KernelError::NotFound)?;
+                let p = pending[slot].unwrap();
+                match p.reply {
+                    Some(reply) => {
+                        pending[slot] = None;
+                        Ok(Some(reply))
+                    }
+                    None => {
+                        // blocked within the critical section, so that a reply cannot be missed.
+                        block_tasks(self.task_manager, 1 << p.requester, BlockReason::EventWait);
+                        Ok(None)
+                    }
+                }
+            })?;
+            match reply {
+                Some(reply) => return Ok(reply),
+                None => schedule(self.task_manager),
+            }
+        }
+    }
+}
+
+unsafe impl<Q: Copy, R: Copy> Sync for RequestReply<Q, R> {}
@@ -2,22 +2,93 @@
 //!
 //! Defines the Kernel routines and primitives for resource management.
 use core::cell::RefCell;
+use core::marker::PhantomData;
+use core::ops::Deref;
 
-use crate::kernel::tasks::{block_tasks, get_curr_tid, schedule, unblock_tasks};
+use crate::kernel::tasks::{block_tasks, get_curr_tid, schedule, unblock_tasks, validate_tasks};
+use crate::config::{MAX_RESOURCES, MAX_TASKS};
 use crate::system::pi_stack::PiStack;
-use crate::system::scheduler::{BooleanVector, Scheduler, TaskId};
-use crate::utils::arch::{critical_section, Mutex};
-use crate::system::spinlock::{spinlock, spinlock_try, spinunlock, TASKMANAGER_LOCK};
-use crate::utils::helpers::get_msb_const;
+use crate::system::scheduler::{BlockReason, BooleanVector, Scheduler, TaskId};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
+use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
+use crate::utils::helpers::{get_msb, get_msb_const};
 use crate::KernelError;
 use cortex_m_semihosting::hprintln;
 
 #[cfg(feature = "system_logger")]
 use {crate::kernel::logging, crate::system::system_logger::LogEventType};
 
+#[cfg(feature = "metrics")]
+use {crate::kernel::timer::get_time, crate::utils::arch::CriticalSection};
+
 /// Global instance of Resource manager
-pub static PiStackGlobal: Mutex<RefCell<PiStack>> = Mutex::new(RefCell::new(PiStack::new()));
-pub static PiStackGlobal_C1: Mutex<RefCell<PiStack>> = Mutex::new(RefCell::new(PiStack::new()));
+pub static PiStackGlobal: KernelCell<PiStack> = KernelCell::new(PiStack::new());
+#[cfg(feature = "multicore")]
+pub static PiStackGlobal_C1: KernelCell<PiStack> = KernelCell::new(PiStack::new());
+
+/// Resources registered to have their configuration validated by `start_kernel`.
+static RESOURCES: KernelCell<[Option<&'static dyn ResourceConfig>; MAX_RESOURCES]> =
+    KernelCell::new([None; MAX_RESOURCES]);
+
+/// A resource whose configuration can be validated before the kernel starts.
+pub trait ResourceConfig: Sync {
+    /// Checks the users and ceilings of the resource, see `Resource::validate`.
+    fn validate(&self) -> Result<(), KernelError>;
+}
+
+/// Registers `resource` to be validated by `start_kernel`. Resources are statics created in
+/// const context, so they cannot register themselves and must be registered after the tasks are
+/// created. Returns `LimitExceeded` if `MAX_RESOURCES` resources are already registered.
+pub fn register(resource: &'static dyn ResourceConfig) -> Result<(), KernelError> {
+    critical_section(|cs_token| {
+        let resources = &mut *RESOURCES.borrow(cs_token).borrow_mut();
+        let slot = resources
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(KernelError::LimitExceeded)?;
+        *slot = Some(resource);
+        Ok(())
+    })
+}
+
+/// Validates every registered resource, the first error found is returned. Called by
+/// `start_kernel`, which refuses to start with a misconfigured resource.
+pub fn validate() -> Result<(), KernelError> {
+    let resources = critical_section(|cs_token| *RESOURCES.borrow(cs_token).borrow());
+    for resource in resources.iter().flatten() {
+        resource.validate()?;
+    }
+    Ok(())
+}
+
+/// Checks that `ceiling` is a valid priority and is exactly the priority of the highest priority
+/// task in `users`, so that no user can preempt a holder of the resource. Returns
+/// `LimitExceeded` otherwise.
+pub(crate) fn validate_ceiling(ceiling: TaskId, users: BooleanVector) -> Result<(), KernelError> {
+    if ceiling as usize >= MAX_TASKS || get_msb(users).unwrap_or(0) != ceiling as usize {
+        return Err(KernelError::LimitExceeded);
+    }
+    Ok(())
+}
+
+/// Returns the current system ceiling of `pi_stack`, the highest ceiling amongst the locked
+/// resources, -1 if no resource is locked.
+pub fn current_system_ceiling(pi_stack: &'static KernelCell<PiStack>) -> i32 {
+    critical_section(|cs_token| pi_stack.borrow(cs_token).borrow().system_ceiling)
+}
+
+/// Returns true if `resource` is currently locked by a task.
+pub fn is_resource_locked<T>(resource: &Resource<T>) -> bool {
+    critical_section(|_| *resource.locked.borrow())
+}
+
+/// Locks `resource` without waiting, `KernelError::ResourceBusy` is returned right away if the
+/// system ceiling would block the caller. The lock is released when the returned guard is dropped.
+/// Same as `Resource::lock`, which never waits under the ceiling protocol either.
+pub fn try_lock_resource<T>(resource: &Resource<T>) -> Result<ResourceGuard<'_, T>, KernelError> {
+    resource.lock()
+}
 
 /// A Safe Container to store a resource, it can hold resource of any Generic Type
 /// and allow safe access to it without ending up in Data races or Deadlocks.
@@ -29,20 +100,33 @@ pub struct Resource<T: Sized> {
     /// It holds the priority of the highest priority task that can access that resource.
     pub(crate) tasks_mask: BooleanVector,
     blocked_mask: RefCell<BooleanVector>,
+    /// The priority inherited by the holder before it locked the resource.
+    prev_inherited: RefCell<Option<usize>>,
+    /// True while the resource is locked.
+    locked: RefCell<bool>,
+    /// The time at which the resource was last locked.
+    #[cfg(feature = "metrics")]
+    lock_time: RefCell<u32>,
+    /// The longest time a task of higher priority than the holder has waited for the resource.
+    #[cfg(feature = "metrics")]
+    max_inversion: RefCell<u32>,
+    /// The longest time the resource has been held locked.
+    #[cfg(feature = "metrics")]
+    max_hold_time: RefCell<u32>,
     /// This field holds the actual resource that has to be locked.
     inner: T,
 
     /// A reference to access the kernel functions
-    pub(crate) task_manager: &'static Mutex<RefCell<Scheduler>>,
+    pub(crate) task_manager: &'static KernelCell<Scheduler>,
     // TODO: put pi_stack in task_manager?
-    pi_stack: &'static Mutex<RefCell<PiStack>>,
+    pi_stack: &'static KernelCell<PiStack>,
 }
 
 impl<T: Sized> Resource<T> {
     /// Create and initialize new Resource object
     pub const fn new(
-        task_manager: &'static Mutex<RefCell<Scheduler>>,
-        pi_stack: &'static Mutex<RefCell<PiStack>>,
+        task_manager: &'static KernelCell<Scheduler>,
+        pi_stack: &'static KernelCell<PiStack>,
         val: T,
         tasks_mask: BooleanVector,
     ) -> Self {
@@ -53,10 +137,23 @@ impl<T: Sized> Resource<T> {
             inner: val,
             tasks_mask: tasks_mask,
             blocked_mask: RefCell::new(0),
+            prev_inherited: RefCell::new(None),
+            locked: RefCell::new(false),
+            #[cfg(feature = "metrics")]
+            lock_time: RefCell::new(0),
+            #[cfg(feature = "metrics")]
+            max_inversion: RefCell::new(0),
+            #[cfg(feature = "metrics")]
+            max_hold_time: RefCell::new(0),
             ceiling: get_msb_const(tasks_mask) as TaskId,
         }
     }
 
+    /// Identifies the resource in errors, its address is stable as resources are statics.
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
     /// Returns the `Pi_mask`, which is just a boolean vector with all bits up to ceiling (including) set to 1.
     fn get_pi_mask(ceiling: TaskId) -> u32 {
         let mask;
@@ -68,8 +165,9 @@ impl<T: Sized> Resource<T> {
         mask
     }
 
-    /// Lock the Resource for the currently running task and blocks the competing tasks
-    pub(crate) fn lock(&self) -> Result<&T, KernelError> {
+    /// Lock the Resource for the currently running task and blocks the competing tasks, the task
+    /// inherits the ceiling of the resource until it unlocks it.
+    pub(crate) fn lock_raw(&self) -> Result<&T, KernelError> {
         critical_section(|cs_token| {
             let pi_stack = &mut self.pi_stack.borrow(cs_token).borrow_mut();
             let curr_tid = get_curr_tid(self.task_manager) as u32;
@@ -85,17 +183,43 @@ impl<T: Sized> Resource<T> {
                 return Err(KernelError::AccessDenied);
             }
             if ceiling as i32 > pi_stack.system_ceiling {
-                pi_stack.push_stack(ceiling)?;
-                // XXX(bitops): self.tasks_mask & !blocked_tasks
+                pi_stack.push_stack(self.id(), ceiling)?;
+                // XXX(bitops): self.tasks_mask & !blocked_for(ResourceWait)
                 // gives the task mask which are the tasks which will be blocked by calling this
                 // specific lock. we use this mask to be unlock the tasks which are blocked by the
-                // lock of this resource.
+                // lock of this resource, the tasks blocked by an outer lock stay blocked.
 
                 spinlock(&TASKMANAGER_LOCK);
-                *self.blocked_mask.borrow_mut() =
-                    self.tasks_mask & !self.task_manager.borrow(cs_token).borrow().blocked_tasks;
+                let mut handler = self.task_manager.borrow(cs_token).borrow_mut();
+                *self.blocked_mask.borrow_mut() = !(1 << curr_tid)
+                    & self.tasks_mask
+                    & !handler.blocked_for(BlockReason::ResourceWait);
+                // the holder runs at the ceiling so that tasks which do not use the resource but
+                // are of lower priority than the ceiling cannot delay it.
+                #[cfg(feature = "system_logger")]
+                let old_priority = handler.effective_priority(curr_tid as usize);
+                *self.prev_inherited.borrow_mut() =
+                    handler.set_inherited_priority(curr_tid as usize, Some(ceiling as usize));
+                #[cfg(feature = "system_logger")]
+                let new_priority = handler.effective_priority(curr_tid as usize);
+                drop(handler);
                 spinunlock(&TASKMANAGER_LOCK);
-                block_tasks(self.task_manager, !(1 << curr_tid) & self.tasks_mask);
+                #[cfg(feature = "system_logger")]
+                {
+                    if new_priority != old_priority && logging::get_priority_inheritance() {
+                        logging::report(LogEventType::PriorityBoost(
+                            curr_tid,
+                            old_priority as u32,
+                            new_priority as u32,
+                        ));
+                    }
+                }
+                block_tasks(self.task_manager, !(1 << curr_tid) & self.tasks_mask, BlockReason::ResourceWait);
+                *self.locked.borrow_mut() = true;
+                #[cfg(feature = "metrics")]
+                {
+                    *self.lock_time.borrow_mut() = get_time();
+                }
                 #[cfg(feature = "system_logger")]
                 {
                     if logging::get_resource_lock() {
@@ -109,16 +233,42 @@ impl<T: Sized> Resource<T> {
             //     ceiling,
             //     pi_stack.system_ceiling
             // );
-            return Err(KernelError::AccessDenied);
+            return Err(KernelError::ResourceBusy);
         })
     }
 
     /// Unlocks the Resource and unblocks the tasks which were blocked during the call to lock
-    pub(crate) fn unlock(&self) -> Result<(), KernelError> {
+    pub(crate) fn unlock_raw(&self) -> Result<(), KernelError> {
         critical_section(|cs_token| {
             let pi_stack = &mut self.pi_stack.borrow(cs_token).borrow_mut();
             if self.ceiling as i32 == pi_stack.system_ceiling {
-                pi_stack.pop_stack().unwrap();
+                pi_stack.pop_stack(self.id(), self.ceiling).unwrap();
+                *self.locked.borrow_mut() = false;
+                spinlock(&TASKMANAGER_LOCK);
+                let mut handler = self.task_manager.borrow(cs_token).borrow_mut();
+                let curr_tid = handler.curr_tid;
+                #[cfg(feature = "system_logger")]
+                let old_priority = handler.effective_priority(curr_tid);
+                handler.set_inherited_priority(curr_tid, *self.prev_inherited.borrow());
+                #[cfg(feature = "system_logger")]
+                let new_priority = handler.effective_priority(curr_tid);
+                drop(handler);
+                spinunlock(&TASKMANAGER_LOCK);
+                #[cfg(feature = "system_logger")]
+                {
+                    if new_priority != old_priority && logging::get_priority_inheritance() {
+                        logging::report(LogEventType::PriorityRestore(
+                            curr_tid as TaskId,
+                            old_priority as u32,
+                            new_priority as u32,
+                        ));
+                    }
+                }
+                #[cfg(feature = "metrics")]
+                {
+                    self.record_inversion(cs_token);
+                    self.record_hold_time();
+                }
             }
             #[cfg(feature = "system_logger")]
             {
@@ -129,20 +279,127 @@ impl<T: Sized> Resource<T> {
                 }
             }
         });
-        unblock_tasks(self.task_manager, *self.blocked_mask.borrow());
+        unblock_tasks(self.task_manager, *self.blocked_mask.borrow(), BlockReason::ResourceWait);
         schedule(self.task_manager);
         Ok(())
     }
-    /// A helper function that ensures that if a resource is locked, it is unlocked.
+    /// Measures how long the tasks of higher priority than the holder, which are ready but for the
+    /// lock, have been waiting for the resource. A task waits from the later of the lock and its
+    /// release.
+    #[cfg(feature = "metrics")]
+    fn record_inversion(&self, cs_token: &CriticalSection) {
+        let curr_time = get_time();
+        let lock_time = *self.lock_time.borrow();
+        spinlock(&TASKMANAGER_LOCK);
+        let handler = self.task_manager.borrow(cs_token).borrow();
+        let waiting = *self.blocked_mask.borrow()
+            & handler.active_tasks
+            & !Self::get_pi_mask(handler.curr_tid as TaskId);
+        let mut max_inversion = *self.max_inversion.borrow();
+        for tid in 0..MAX_TASKS {
+            if waiting & (1 << tid) > 0 {
+                let wait_start = match handler.release_times[tid] {
+                    Some(release_time) if release_time.wrapping_sub(lock_time) as i32 > 0 => {
+                        release_time
+                    }
+                    _ => lock_time,
+                };
+                let inversion = curr_time.wrapping_sub(wait_start);
+                if inversion > max_inversion {
+                    max_inversion = inversion;
+                }
+            }
+        }
+        drop(handler);
+        spinunlock(&TASKMANAGER_LOCK);
+        *self.max_inversion.borrow_mut() = max_inversion;
+    }
+
+    /// Returns the longest priority inversion caused by the resource in ticks.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn max_inversion(&self) -> u32 {
+        critical_section(|_| *self.max_inversion.borrow())
+    }
+
+    /// Updates the longest hold time with the time elapsed since the resource was locked.
+    #[cfg(feature = "metrics")]
+    fn record_hold_time(&self) {
+        let hold_time = get_time().wrapping_sub(*self.lock_time.borrow());
+        let max_hold_time = &mut *self.max_hold_time.borrow_mut();
+        if hold_time > *max_hold_time {
+            *max_hold_time = hold_time;
+        }
+    }
+
+    /// Returns the longest time the resource has been held locked in ticks.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn max_hold_time(&self) -> u32 {
+        critical_section(|_| *self.max_hold_time.borrow())
+    }
+
+    /// Validates the configuration of the resource: the ceiling must be a priority below
+    /// `MAX_TASKS` equal to that of the highest priority user, and all the tasks which can access
+    /// the resource must have been created. Runs from `start_kernel` for registered resources.
+    pub fn validate(&self) -> Result<(), KernelError> {
+        validate_ceiling(self.ceiling, self.tasks_mask)?;
+        validate_tasks(self.task_manager, self.tasks_mask)
+    }
+
+    /// Locks the resource for the currently running task, the resource is unlocked and the tasks
+    /// blocked by the lock are rescheduled when the returned guard is dropped. A guard which is
+    /// leaked (e.g. by `mem::forget`) keeps the resource locked, like any other lock held forever.
+    /// The guard must not be held across `task_exit`, which cannot be enforced at compile time.
+    /// Locking never waits under the ceiling protocol, `ResourceBusy` is returned if the system
+    /// ceiling does not allow the resource to be locked.
+    pub fn lock(&self) -> Result<ResourceGuard<'_, T>, KernelError> {
+        let inner = self.lock_raw()?;
+        Ok(ResourceGuard {
+            resource: self,
+            inner,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// A helper function that ensures that if a resource is locked, it is unlocked. Returns
+    /// `ResourceBusy` if the system ceiling does not allow the resource to be locked, and
+    /// `AccessDenied` if the running task cannot access the resource.
     pub fn acquire<F, R>(&self, handler: F) -> Result<R, KernelError>
     where
         F: Fn(&T) -> R,
     {
-        let value = self.lock()?;
+        let value = self.lock_raw()?;
         let res = handler(value);
-        self.unlock()?;
+        self.unlock_raw()?;
         return Ok(res);
     }
 }
 
 unsafe impl<T> Sync for Resource<T> {}
+
+impl<T> ResourceConfig for Resource<T> {
+    fn validate(&self) -> Result<(), KernelError> {
+        Resource::validate(self)
+    }
+}
+
+/// Access to a locked resource, the resource is unlocked when the guard is dropped. The guard cannot
+/// be sent to another task as the lock belongs to the task which locked it.
+pub struct ResourceGuard<'a, T: Sized> {
+    resource: &'a Resource<T>,
+    inner: &'a T,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T: Sized> Deref for ResourceGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.inner
+    }
+}
+
+impl<T: Sized> Drop for ResourceGuard<'_, T> {
+    fn drop(&mut self) {
+        self.resource.unlock_raw().ok();
+    }
+}
@@ -0,0 +1,80 @@
+//! # Ring Buffer
+//! A bounded FIFO queue which does not allocate, shared by the kernel primitives which buffer items.
+
+/// A FIFO queue of at most `N` items of type `T`, stored inline.
+#[derive(Clone, Copy)]
+pub struct RingBuffer<T: Copy, const N: usize> {
+    buf: [Option<T>; N],
+    /// Index of the oldest item.
+    head: usize,
+    /// Number of items held.
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    /// Returns an empty ring buffer, usable to initialize a `static`.
+    pub const fn new() -> Self {
+        Self {
+            buf: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of items held.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Appends `item` after the newest item, the item is handed back if the buffer is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        self.buf[(self.head + self.len) % N] = Some(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Appends `item` after the newest item, discarding the oldest item if the buffer is full.
+    pub fn push_overwrite(&mut self, item: T) {
+        if self.is_full() {
+            self.pop();
+        }
+        let _ = self.push(item);
+    }
+
+    /// Removes and returns the oldest item, `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let item = self.buf[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        item
+    }
+
+    /// Returns the oldest item without removing it, `None` if the buffer is empty.
+    pub fn peek(&self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.buf[self.head]
+    }
+
+    /// Removes all the items.
+    pub fn clear(&mut self) {
+        self.buf = [None; N];
+        self.head = 0;
+        self.len = 0;
+    }
+}
@@ -0,0 +1,210 @@
+//! # Reader-Writer Resource
+//!
+//! Defines a resource which can be held by multiple readers at once or by a single writer.
+use core::cell::RefCell;
+
+use crate::kernel::tasks::{block_tasks, get_curr_tid, schedule, unblock_tasks, validate_tasks};
+use crate::system::pi_stack::PiStack;
+use crate::system::resource::{validate_ceiling, ResourceConfig};
+use crate::system::scheduler::{BlockReason, BooleanVector, Scheduler, TaskId};
+use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
+use crate::utils::helpers::get_msb_const;
+use crate::KernelError;
+
+#[cfg(feature = "system_logger")]
+use {crate::kernel::logging, crate::system::system_logger::LogEventType};
+
+/// A Safe Container to store a resource that is read by many tasks but written by few.
+/// Readers do not block each other, a read lock only blocks the writers. A write lock blocks
+/// all readers and writers.
+pub struct RwResource<T: Sized> {
+    /// A boolean vector holding which tasks can read the resource.
+    readers_mask: BooleanVector,
+    /// A boolean vector holding which tasks can write (and read) the resource.
+    writers_mask: BooleanVector,
+    /// Priority of the highest priority writer, the ceiling raised by a read lock.
+    read_ceiling: TaskId,
+    /// Priority of the highest priority reader or writer, the ceiling raised by a write lock.
+    write_ceiling: TaskId,
+    /// Number of readers currently holding the resource.
+    reader_count: RefCell<u32>,
+    /// True if locking the resource raised the system ceiling.
+    ceiling_pushed: RefCell<bool>,
+    blocked_mask: RefCell<BooleanVector>,
+    /// This field holds the actual resource that has to be locked.
+    inner: RefCell<T>,
+
+    /// A reference to access the kernel functions
+    task_manager: &'static KernelCell<Scheduler>,
+    pi_stack: &'static KernelCell<PiStack>,
+}
+
+impl<T: Sized> RwResource<T> {
+    /// Create and initialize new RwResource object
+    pub const fn new(
+        task_manager: &'static KernelCell<Scheduler>,
+        pi_stack: &'static KernelCell<PiStack>,
+        val: T,
+        readers_mask: BooleanVector,
+        writers_mask: BooleanVector,
+    ) -> Self {
+        Self {
+            task_manager,
+            pi_stack,
+            inner: RefCell::new(val),
+            readers_mask,
+            writers_mask,
+            read_ceiling: get_msb_const(writers_mask) as TaskId,
+            write_ceiling: get_msb_const(readers_mask | writers_mask) as TaskId,
+            reader_count: RefCell::new(0),
+            ceiling_pushed: RefCell::new(false),
+            blocked_mask: RefCell::new(0),
+        }
+    }
+
+    /// Identifies the resource in errors, its address is stable as resources are statics.
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Locks the resource for the currently running task. A read lock taken while other readers
+    /// hold the resource only increments the reader count.
+    fn lock(&self, is_write: bool) -> Result<(), KernelError> {
+        critical_section(|cs_token| {
+            let curr_tid = get_curr_tid(self.task_manager) as u32;
+            let pid_mask = 1 << curr_tid;
+            let (users, ceiling, competing) = if is_write {
+                (
+                    self.writers_mask,
+                    self.write_ceiling,
+                    self.readers_mask | self.writers_mask,
+                )
+            } else {
+                (
+                    self.readers_mask | self.writers_mask,
+                    self.read_ceiling,
+                    self.writers_mask,
+                )
+            };
+            if users & pid_mask != pid_mask {
+                return Err(KernelError::AccessDenied);
+            }
+            if !is_write && *self.reader_count.borrow() > 0 {
+                // writers are already blocked by the first reader.
+                *self.reader_count.borrow_mut() += 1;
+                return Ok(());
+            }
+
+            let pi_stack = &mut self.pi_stack.borrow(cs_token).borrow_mut();
+            let pushed = ceiling as i32 > pi_stack.system_ceiling;
+            if pushed {
+                pi_stack.push_stack(self.id(), ceiling)?;
+            }
+            *self.ceiling_pushed.borrow_mut() = pushed;
+
+            spinlock(&TASKMANAGER_LOCK);
+            *self.blocked_mask.borrow_mut() = competing
+                & !pid_mask
+                & !self
+                    .task_manager
+                    .borrow(cs_token)
+                    .borrow()
+                    .blocked_for(BlockReason::ResourceWait);
+            spinunlock(&TASKMANAGER_LOCK);
+            block_tasks(self.task_manager, competing & !pid_mask, BlockReason::ResourceWait);
+            if !is_write {
+                *self.reader_count.borrow_mut() = 1;
+            }
+            #[cfg(feature = "system_logger")]
+            {
+                if logging::get_resource_lock() {
+                    logging::report(LogEventType::ResourceLock(curr_tid));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Unlocks the resource, the tasks blocked during the lock are unblocked once the last holder unlocks.
+    fn unlock(&self, is_write: bool) -> Result<(), KernelError> {
+        let is_last = critical_section(|cs_token| {
+            if !is_write {
+                let reader_count = &mut *self.reader_count.borrow_mut();
+                *reader_count -= 1;
+                if *reader_count > 0 {
+                    return Ok(false);
+                }
+            }
+            if *self.ceiling_pushed.borrow() {
+                let ceiling = if is_write {
+                    self.write_ceiling
+                } else {
+                    self.read_ceiling
+                };
+                self.pi_stack
+                    .borrow(cs_token)
+                    .borrow_mut()
+                    .pop_stack(self.id(), ceiling)?;
+            }
+            #[cfg(feature = "system_logger")]
+            {
+                if logging::get_resource_unlock() {
+                    logging::report(LogEventType::ResourceUnlock(
+                        get_curr_tid(self.task_manager) as u32,
+                    ));
+                }
+            }
+            Ok(true)
+        })?;
+        if is_last {
+            unblock_tasks(
+                self.task_manager,
+                *self.blocked_mask.borrow(),
+                BlockReason::ResourceWait,
+            );
+            schedule(self.task_manager);
+        }
+        Ok(())
+    }
+
+    /// Validates the configuration of the resource: the read ceiling must be the priority of the
+    /// highest priority writer, the write ceiling that of the highest priority user, and all the
+    /// readers and writers must have been created. Runs from `start_kernel` once registered.
+    pub fn validate(&self) -> Result<(), KernelError> {
+        validate_ceiling(self.read_ceiling, self.writers_mask)?;
+        validate_ceiling(self.write_ceiling, self.readers_mask | self.writers_mask)?;
+        validate_tasks(self.task_manager, self.readers_mask | self.writers_mask)
+    }
+
+    /// Locks the resource for reading and passes a shared reference to `handler`.
+    pub fn read<F, R>(&self, handler: F) -> Result<R, KernelError>
+    where
+        F: Fn(&T) -> R,
+    {
+        self.lock(false)?;
+        let res = handler(&self.inner.borrow());
+        self.unlock(false)?;
+        return Ok(res);
+    }
+
+    /// Locks the resource for writing and passes a mutable reference to `handler`.
+    pub fn write<F, R>(&self, handler: F) -> Result<R, KernelError>
+    where
+        F: Fn(&mut T) -> R,
+    {
+        self.lock(true)?;
+        let res = handler(&mut self.inner.borrow_mut());
+        self.unlock(true)?;
+        return Ok(res);
+    }
+}
+
+unsafe impl<T> Sync for RwResource<T> {}
+
+impl<T> ResourceConfig for RwResource<T> {
+    fn validate(&self) -> Result<(), KernelError> {
+        RwResource::validate(self)
+    }
+}
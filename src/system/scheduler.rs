@@ -1,16 +1,57 @@
 //! # Task Manager
 //! The Definition of Data-structures required for task management.
 //!
-use crate::config::MAX_TASKS;
-use crate::utils::arch::{get_msb, load_context, save_context, wait_for_interrupt};
+use crate::config::{MAX_TASKS, RESERVED_ISR_STACK};
+#[cfg(not(feature = "edf"))]
+use crate::config::MAX_PREEMPT_NESTING;
+#[cfg(feature = "round_robin")]
+use crate::config::RR_QUANTUM;
+#[cfg(feature = "stack_check")]
+use crate::config::STACK_CANARY;
+use crate::utils::arch::{idle, load_context, save_context};
+use crate::utils::helpers::get_msb;
 use crate::KernelError;
 
 #[cfg(feature = "task_monitor")]
-use crate::kernel::task_monitor::{clear_deadline, set_deadline};
+use crate::kernel::task_monitor::{clear_deadline, move_task, set_deadline};
+
+#[cfg(feature = "system_logger")]
+use {crate::kernel::logging, crate::system::system_logger::LogEventType};
 
 pub type TaskId = u32;
 pub type BooleanVector = u32;
 
+/// Every task is a bit of the `BooleanVector`s of the scheduler, hence `MAX_TASKS` cannot exceed
+/// the width of a `BooleanVector`. The build fails otherwise.
+const _: [(); 0 - !(MAX_TASKS <= 8 * core::mem::size_of::<BooleanVector>()) as usize] = [];
+
+/// Why a task is blocked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockReason {
+    /// Blocked while a resource it can access is locked.
+    ResourceWait,
+    /// Blocked explicitly, through a task handle or a transaction.
+    Suspended,
+    /// Blocked until its wake up time.
+    Sleeping,
+    /// Blocked until another task exits.
+    Join,
+    /// Blocked until a semaphore is posted, a message is sent or a reply arrives.
+    EventWait,
+    /// Blocked until the slot of a task is freed or a timeout elapses.
+    SlotWait,
+}
+
+/// Every `BlockReason`, in declaration order, which is also the index of its mask in `block_masks`.
+const BLOCK_REASONS: [BlockReason; 6] = [
+    BlockReason::ResourceWait,
+    BlockReason::Suspended,
+    BlockReason::Sleeping,
+    BlockReason::Join,
+    BlockReason::EventWait,
+    BlockReason::SlotWait,
+];
+
 /// Maintains state of all tasks in the Kernel
 #[repr(C)]
 // #[derive(Debug)]
@@ -23,6 +64,12 @@ pub struct Scheduler {
     pub task_control_blocks: [Option<TaskControlBlock>; MAX_TASKS],
     /// A boolean vector in which, if a bit at a position is true, it implies that the task is blocked and cannot be scheduled even if it’s active.
     pub blocked_tasks: BooleanVector,
+    /// Why each task was blocked first among the reasons still holding it, `None` if the task is not
+    /// blocked.
+    pub block_reasons: [Option<BlockReason>; MAX_TASKS],
+    /// For each `BlockReason`, the tasks blocked for it. A task can be blocked for several reasons at
+    /// once, `blocked_tasks` is the union of these masks.
+    block_masks: [BooleanVector; BLOCK_REASONS.len()],
     /// A boolean vector in which, if a bit at a position is true, it implies that the task is active and to be scheduled.
     pub active_tasks: BooleanVector,
     pub migrated_tasks: BooleanVector,
@@ -31,6 +78,57 @@ pub struct Scheduler {
     /// A variable which decided if the scheduler should preemptively schedule tasks or not.
     pub is_preemptive: bool,
     pub preempt_disable_count: u32,
+    /// True if the kernel idle task occupies priority 0.
+    pub has_idle: bool,
+    /// For each task, a boolean vector of the tasks blocked until it exits.
+    pub joiners: [BooleanVector; MAX_TASKS],
+    /// For each slot, a boolean vector of the tasks blocked until the slot is freed.
+    #[cfg(feature = "timer")]
+    pub slot_waiters: [BooleanVector; MAX_TASKS],
+    /// The time at which each task was last released, `None` if it has never been released.
+    #[cfg(feature = "timer")]
+    pub release_times: [Option<u32>; MAX_TASKS],
+    /// The time at which each sleeping task has to be woken up, `None` if the task is not sleeping.
+    #[cfg(feature = "timer")]
+    pub wake_times: [Option<u32>; MAX_TASKS],
+    /// The period of each periodic task in ticks, `None` if the task is not periodic.
+    #[cfg(feature = "timer")]
+    pub periods: [Option<u32>; MAX_TASKS],
+    /// The time of the next release of each periodic task.
+    #[cfg(feature = "timer")]
+    pub next_releases: [u32; MAX_TASKS],
+    /// The number of periods each periodic task was still running when its next period arrived.
+    #[cfg(feature = "timer")]
+    pub missed_periods: [u32; MAX_TASKS],
+    /// Human readable name of each task, indexed by priority.
+    pub task_names: [&'static str; MAX_TASKS],
+    /// For each task, a boolean vector of the tasks released when it exits.
+    pub successors: [BooleanVector; MAX_TASKS],
+    /// A boolean vector of the tasks which have exited but are not yet cleaned up by the reaper.
+    pub zombie_tasks: BooleanVector,
+    /// The task which cleans up after exited tasks, if `None` tasks clean up in `task_exit`.
+    pub reaper: Option<usize>,
+    /// The priority each task has inherited from the resources it holds, `None` if it holds none.
+    pub inherited_priorities: [Option<usize>; MAX_TASKS],
+    /// A boolean vector of the tasks which were preempted while ready and have not resumed yet, each
+    /// of which holds an exception frame on its stack. The idle task is not counted.
+    pub preempted_tasks: BooleanVector,
+    /// The number of times each task has been dispatched.
+    #[cfg(feature = "metrics")]
+    pub dispatch_counts: [u32; MAX_TASKS],
+    /// True while the scheduler is frozen, the running task keeps the CPU until it is unfrozen.
+    pub maintenance: bool,
+    /// The privilege of thread mode when the scheduler started, which tasks start with.
+    pub thread_privileged: bool,
+    /// The round-robin band of each task, identified by its highest priority task.
+    #[cfg(feature = "round_robin")]
+    pub bands: [usize; MAX_TASKS],
+    /// For each band, the task whose turn it is to run.
+    #[cfg(feature = "round_robin")]
+    pub band_turns: [usize; MAX_TASKS],
+    /// The number of ticks the running task has used of its quantum, reset on every dispatch.
+    #[cfg(feature = "round_robin")]
+    pub slice_used: u32,
 }
 
 /// A single tasks's state
@@ -40,6 +138,19 @@ pub struct Scheduler {
 pub struct TaskControlBlock {
     /// Holds a reference to the stack pointer for the task.
     stack_pointer: usize, // current stack pointer of this thread
+    /// Only tasks with a priority higher than this threshold can preempt the task.
+    preempt_threshold: usize,
+    /// Address of the lowest word of the task's stack, which holds the stack canary.
+    #[cfg(feature = "stack_check")]
+    stack_bottom: usize,
+    /// Size of the task's stack in words.
+    #[cfg(feature = "stack_check")]
+    stack_len: usize,
+    /// True if the task may raise the privilege of thread mode with `with_privilege`.
+    may_elevate: bool,
+    /// The privilege of thread mode when the task was last switched out, `None` if it has not run
+    /// yet, in which case it starts with the privilege thread mode had when the kernel started.
+    privileged: Option<bool>,
 }
 
 #[cfg(feature = "task_monitor")]
@@ -48,6 +159,19 @@ pub struct TaskControlBlock {
 pub struct TaskControlBlock {
     /// Holds a reference to the stack pointer for the task.
     stack_pointer: usize, // current stack pointer of this thread
+    /// Only tasks with a priority higher than this threshold can preempt the task.
+    preempt_threshold: usize,
+    /// Address of the lowest word of the task's stack, which holds the stack canary.
+    #[cfg(feature = "stack_check")]
+    stack_bottom: usize,
+    /// Size of the task's stack in words.
+    #[cfg(feature = "stack_check")]
+    stack_len: usize,
+    /// True if the task may raise the privilege of thread mode with `with_privilege`.
+    may_elevate: bool,
+    /// The privilege of thread mode when the task was last switched out, `None` if it has not run
+    /// yet, in which case it starts with the privilege thread mode had when the kernel started.
+    privileged: Option<bool>,
     deadline: u32,
 }
 
@@ -58,6 +182,41 @@ impl TaskControlBlock {
     pub fn load_context(&self) {
         load_context(self)
     }
+
+    /// Returns false if the canary at the bottom of the task's stack was overwritten, or if the saved
+    /// stack pointer reaches the canary. The latter catches an overflow which skipped the canary, e.g.
+    /// a large stack frame, as soon as the context is saved.
+    #[cfg(feature = "stack_check")]
+    pub fn is_stack_intact(&self) -> bool {
+        let stack_pointer = unsafe { core::ptr::read_volatile(&self.stack_pointer) };
+        stack_pointer > self.stack_bottom
+            && unsafe { core::ptr::read_volatile(self.stack_bottom as *const u32) == STACK_CANARY }
+    }
+
+    /// Records the privilege of thread mode as the privilege of the task, called when the task is
+    /// switched out.
+    pub fn save_privilege(&mut self, privileged: bool) {
+        self.privileged = Some(privileged);
+    }
+
+    /// Returns the privilege of thread mode the task is to run with, `default` if it has not run yet.
+    pub fn privilege(&self, default: bool) -> bool {
+        self.privileged.unwrap_or(default)
+    }
+
+    /// Returns the highest number of words of the task's stack ever used, found by walking up from
+    /// the bottom of the stack over the words which still hold the paint.
+    #[cfg(feature = "stack_check")]
+    pub fn stack_usage(&self) -> usize {
+        let bottom = self.stack_bottom as *const u32;
+        let mut unused = 0;
+        while unused < self.stack_len
+            && unsafe { core::ptr::read_volatile(bottom.add(unused)) } == STACK_CANARY
+        {
+            unused += 1;
+        }
+        self.stack_len - unused
+    }
 }
 
 impl Scheduler {
@@ -69,11 +228,43 @@ impl Scheduler {
             task_control_blocks: [None; MAX_TASKS],
             active_tasks: 1,
             blocked_tasks: 0,
+            block_reasons: [None; MAX_TASKS],
+            block_masks: [0; BLOCK_REASONS.len()],
             migrated_tasks: 0,
             migrated_tid: 0,
             running_migrated: false,
             is_preemptive: false,
             preempt_disable_count: 0,
+            has_idle: true,
+            joiners: [0; MAX_TASKS],
+            #[cfg(feature = "timer")]
+            slot_waiters: [0; MAX_TASKS],
+            #[cfg(feature = "timer")]
+            release_times: [None; MAX_TASKS],
+            #[cfg(feature = "timer")]
+            wake_times: [None; MAX_TASKS],
+            #[cfg(feature = "timer")]
+            periods: [None; MAX_TASKS],
+            #[cfg(feature = "timer")]
+            next_releases: [0; MAX_TASKS],
+            #[cfg(feature = "timer")]
+            missed_periods: [0; MAX_TASKS],
+            task_names: [""; MAX_TASKS],
+            successors: [0; MAX_TASKS],
+            zombie_tasks: 0,
+            reaper: None,
+            inherited_priorities: [None; MAX_TASKS],
+            preempted_tasks: 0,
+            #[cfg(feature = "metrics")]
+            dispatch_counts: [0; MAX_TASKS],
+            maintenance: false,
+            thread_privileged: true,
+            #[cfg(feature = "round_robin")]
+            bands: Self::own_bands(),
+            #[cfg(feature = "round_robin")]
+            band_turns: Self::own_bands(),
+            #[cfg(feature = "round_robin")]
+            slice_used: 0,
         }
     }
 
@@ -81,9 +272,9 @@ impl Scheduler {
     pub fn init(&mut self) -> Result<(), KernelError> {
         self.is_preemptive = true;
 
-        static mut stack0: [u32; 64] = [0; 64];
+        static mut stack0: [u32; 128] = [0; 128];
         self.create_task(0, 100, unsafe { &mut stack0 }, || loop {
-            wait_for_interrupt();
+            idle();
         })
     }
 
@@ -93,12 +284,20 @@ impl Scheduler {
 
         static mut stack0: [u32; 64] = [0; 64];
         self.create_task(0, unsafe { &mut stack }, || loop {
-            wait_for_interrupt();
+            idle();
         })
     }
 
+    /// Initializes the scheduler without the idle task, priority 0 is available to the application.
+    /// The application must guarantee that at least one task is always ready.
+    pub fn init_no_idle(&mut self, is_preemptive: bool) {
+        self.is_preemptive = is_preemptive;
+        self.has_idle = false;
+        self.active_tasks = 0;
+    }
+
     /// The program counter for the task is pointer value of the function pointer (`handler_fn`). param is a variable whose reference will be made accessible to the task, and this helps in sharing global state with other tasks. Both these values are stored in a specific index of the stack so that when the context\_switch function loads the stack for this task, the appropriate program counter and argument for that function is loaded.
-    /// An important thing to note is that the task’s index in the `task_control_blocks` is the priority of the task. Hence there can be only one task of the mentioned priority. Also, another important thing is that the argument param is of a generic type(`T`).
+    /// An important thing to note is that the task’s index in the `task_control_blocks` is the priority of the task. Hence there can be only one task of the mentioned priority, `KernelError::Exists` is returned if the priority is already taken (a task which has exited can be removed with `destroy_task` first). Also, another important thing is that the argument param is of a generic type(`T`).
     ///
     /// The `<T: Sync>` informs the compiler that the type `T` must implement the Sync trait. By implementing the Sync trait, a type becomes safe to be shared across tasks. Hence if a type that doesn’t implement Sync trait (like a mutable integer) is passed as param, then the code won’t compile. Kernel primitives like Message and Resource (which are data race safe) implement the Sync trait; hence, it can be passed as param. In this way, the Kernel makes safety a requirement rather than a choice.
     ///
@@ -133,19 +332,16 @@ impl Scheduler {
         stack: &mut [u32],
         handler: fn() -> !,
     ) -> Result<TaskControlBlock, KernelError> {
-        if stack.len() < 32 {
-            return Err(KernelError::StackTooSmall);
-        }
-
-        let pos = stack.len() - 1;
-        let pc: usize = handler as usize;
-
-        stack[pos] = 1 << 24; // xPSR
-        stack[pos - 1] = pc as u32; // PC
-
-        let stack_pointer: usize = unsafe { core::intrinsics::transmute(&stack[stack.len() - 16]) };
+        let stack_pointer = Self::init_stack(stack, handler)?;
         let tcb = TaskControlBlock {
             stack_pointer: stack_pointer as usize,
+            preempt_threshold: 0,
+            #[cfg(feature = "stack_check")]
+            stack_bottom: stack.as_ptr() as usize,
+            #[cfg(feature = "stack_check")]
+            stack_len: stack.len(),
+            may_elevate: false,
+            privileged: None,
         };
 
         Ok(tcb)
@@ -158,63 +354,811 @@ impl Scheduler {
         stack: &mut [u32],
         handler: fn() -> !,
     ) -> Result<TaskControlBlock, KernelError> {
-        if stack.len() < 32 {
-            return Err(KernelError::StackTooSmall);
-        }
-
-        let pos = stack.len() - 1;
-        let pc: usize = handler as usize;
-
-        stack[pos] = 1 << 24; // xPSR
-        stack[pos - 1] = pc as u32; // PC
-
-        let stack_pointer: usize = unsafe { core::intrinsics::transmute(&stack[stack.len() - 16]) };
+        let stack_pointer = Self::init_stack(stack, handler)?;
         let tcb = TaskControlBlock {
             deadline,
             stack_pointer: stack_pointer as usize,
+            preempt_threshold: 0,
+            #[cfg(feature = "stack_check")]
+            stack_bottom: stack.as_ptr() as usize,
+            #[cfg(feature = "stack_check")]
+            stack_len: stack.len(),
+            may_elevate: false,
+            privileged: None,
         };
 
         Ok(tcb)
     }
 
+    /// Writes the initial exception frame of the task onto `stack` and returns the initial stack pointer.
+    /// AAPCS requires the exception frame to be 8-byte aligned, hence the frame is placed at the
+    /// highest 8-byte aligned address within `stack`, which may leave the last word unused.
+    /// The stack must also have `RESERVED_ISR_STACK` words to spare for nested interrupts.
+    /// With `stack_check`, the stack below the frame is painted with the stack canary, the lowest word
+    /// of which is the canary checked on context switches.
+    fn init_stack(stack: &mut [u32], handler: fn() -> !) -> Result<usize, KernelError> {
+        let end = stack.as_ptr() as usize + stack.len() * 4;
+        let top = stack.len().saturating_sub((end % 8) / 4);
+        if top < 32 + RESERVED_ISR_STACK {
+            return Err(KernelError::StackTooSmall);
+        }
+
+        #[cfg(feature = "stack_check")]
+        {
+            for word in stack[..top - 16].iter_mut() {
+                *word = STACK_CANARY;
+            }
+        }
+
+        let pc: usize = handler as usize;
+
+        stack[top - 1] = 1 << 24; // xPSR
+        stack[top - 2] = pc as u32; // PC
+
+        Ok(&stack[top - 16] as *const u32 as usize)
+    }
+
     /// Inserts the `TCB` into `task_control_blocks` at position `id`.
-    fn insert_tcb(&mut self, id: usize, tcb: TaskControlBlock) -> Result<(), KernelError> {
+    /// The preemption threshold of the task defaults to its priority. Once created, the idle task
+    /// cannot be replaced.
+    fn insert_tcb(&mut self, id: usize, mut tcb: TaskControlBlock) -> Result<(), KernelError> {
         if id >= MAX_TASKS {
-            return Err(KernelError::NotFound);
+            return Err(KernelError::LimitExceeded);
         }
+        if self.has_idle && id == 0 && self.task_control_blocks[0].is_some() {
+            return Err(KernelError::AccessDenied);
+        }
+        if self.task_control_blocks[id].is_some() {
+            return Err(KernelError::Exists);
+        }
+        tcb.preempt_threshold = id;
         self.task_control_blocks[id] = Some(tcb);
         return Ok(());
     }
 
-    /// Appends `tasks_mask` onto `blocked_tasks`.
-    pub fn block_tasks(&mut self, tasks_mask: BooleanVector) {
+    /// Allows or forbids the task `tid` to raise the privilege of thread mode with `with_privilege`.
+    pub fn set_may_elevate(&mut self, tid: usize, allowed: bool) -> Result<(), KernelError> {
+        let tcb = self
+            .task_control_blocks
+            .get_mut(tid)
+            .and_then(|tcb| tcb.as_mut())
+            .ok_or(KernelError::NotFound)?;
+        tcb.may_elevate = allowed;
+        Ok(())
+    }
+
+    /// Returns true if the currently running task may raise the privilege of thread mode.
+    pub fn may_elevate(&self) -> bool {
+        match self.task_control_blocks[self.curr_tid] {
+            Some(tcb) => self.started && tcb.may_elevate,
+            None => false,
+        }
+    }
+
+    /// Returns the highest number of words of its stack the task `tid` has used.
+    #[cfg(feature = "stack_check")]
+    pub fn stack_usage(&self, tid: usize) -> Result<usize, KernelError> {
+        if tid >= MAX_TASKS {
+            return Err(KernelError::NotFound);
+        }
+        self.task_control_blocks[tid]
+            .as_ref()
+            .map(|tcb| tcb.stack_usage())
+            .ok_or(KernelError::NotFound)
+    }
+
+    /// Checks that every task in `tasks_mask`, other than the idle task, is a valid task which has
+    /// been created.
+    pub fn validate_tasks(&self, tasks_mask: BooleanVector) -> Result<(), KernelError> {
+        if tasks_mask.checked_shr(MAX_TASKS as u32).unwrap_or(0) != 0 {
+            return Err(KernelError::LimitExceeded);
+        }
+        for tid in 1..MAX_TASKS {
+            if tasks_mask & (1 << tid) > 0 && self.task_control_blocks[tid].is_none() {
+                return Err(KernelError::NotFound);
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks the tasks of `tasks_mask` for `reason`, on top of any other reason they are blocked
+    /// for. The idle task is never blocked, so that the ready set is never empty. The reported
+    /// reason of a task which was blocked already does not change.
+    pub fn block_tasks(&mut self, tasks_mask: BooleanVector, reason: BlockReason) {
+        let idle_mask = if self.has_idle { 1 } else { 0 };
+        let tasks_mask = tasks_mask & !idle_mask;
+        let newly_blocked = tasks_mask & !self.blocked_tasks;
+        self.block_masks[reason as usize] |= tasks_mask;
         self.blocked_tasks |= tasks_mask;
+        for tid in 0..MAX_TASKS {
+            if newly_blocked & (1 << tid) > 0 {
+                self.block_reasons[tid] = Some(reason);
+            }
+        }
+    }
+
+    /// Releases `reason` for the tasks of `tasks_mask`. A task is removed from `blocked_tasks` only
+    /// once every reason it was blocked for has been released.
+    pub fn unblock_tasks(&mut self, tasks_mask: BooleanVector, reason: BlockReason) {
+        let released = tasks_mask & self.block_masks[reason as usize];
+        self.block_masks[reason as usize] &= !released;
+        self.blocked_tasks = self.block_masks.iter().fold(0, |blocked, mask| blocked | mask);
+        for tid in 0..MAX_TASKS {
+            if released & (1 << tid) > 0 && self.block_reasons[tid] == Some(reason) {
+                self.block_reasons[tid] = BLOCK_REASONS
+                    .iter()
+                    .copied()
+                    .find(|other| self.block_masks[*other as usize] & (1 << tid) > 0);
+            }
+        }
+    }
+
+    /// Returns the tasks blocked for `reason`, whether or not they are blocked for other reasons.
+    pub fn blocked_for(&self, reason: BlockReason) -> BooleanVector {
+        self.block_masks[reason as usize]
+    }
+
+    /// Blocks the currently running task until the task `tid` exits. Returns `false` if `tid` is not
+    /// active, in which case the current task is not blocked. A task cannot join itself nor the idle
+    /// task, which never exit.
+    pub fn join(&mut self, tid: usize) -> Result<bool, KernelError> {
+        if tid >= MAX_TASKS || self.task_control_blocks[tid].is_none() {
+            return Err(KernelError::NotFound);
+        }
+        if tid == self.curr_tid || (self.has_idle && tid == 0) {
+            return Err(KernelError::AccessDenied);
+        }
+        if self.active_tasks & (1 << tid) == 0 {
+            return Ok(false);
+        }
+        let curr_mask = 1 << self.curr_tid;
+        self.joiners[tid] |= curr_mask;
+        self.block_tasks(curr_mask, BlockReason::Join);
+        Ok(true)
+    }
+
+    /// Blocks the currently running task until `wake_time`. The idle task cannot sleep as no task
+    /// would be left to run.
+    #[cfg(feature = "timer")]
+    pub fn sleep(&mut self, wake_time: u32) -> Result<(), KernelError> {
+        if self.has_idle && self.curr_tid == 0 {
+            return Err(KernelError::AccessDenied);
+        }
+        self.wake_times[self.curr_tid] = Some(wake_time);
+        self.block_tasks(1 << self.curr_tid, BlockReason::Sleeping);
+        Ok(())
+    }
+
+    /// Blocks the currently running task until the slot `tid` is freed or until `wake_time`. Returns
+    /// `false` if `wake_time` has already been reached at `curr_time`, in which case the current task
+    /// is not blocked.
+    #[cfg(feature = "timer")]
+    pub fn wait_for_slot(&mut self, tid: usize, wake_time: u32, curr_time: u32) -> Result<bool, KernelError> {
+        if tid >= MAX_TASKS {
+            return Err(KernelError::LimitExceeded);
+        }
+        if self.has_idle && self.curr_tid == 0 {
+            return Err(KernelError::AccessDenied);
+        }
+        if curr_time.wrapping_sub(wake_time) as i32 >= 0 {
+            return Ok(false);
+        }
+        let curr_mask = 1 << self.curr_tid;
+        self.slot_waiters[tid] |= curr_mask;
+        self.wake_times[self.curr_tid] = Some(wake_time);
+        self.block_tasks(curr_mask, BlockReason::SlotWait);
+        Ok(true)
+    }
+
+    /// Removes the currently running task from the waiters of the slot `tid`, once it has been woken
+    /// up either by the slot being freed or by its timeout.
+    #[cfg(feature = "timer")]
+    pub fn stop_waiting_for_slot(&mut self, tid: usize) {
+        self.slot_waiters[tid] &= !(1 << self.curr_tid);
+        self.wake_times[self.curr_tid] = None;
+    }
+
+    /// Unblocks the sleeping tasks, and the tasks waiting for a slot, whose wake up time has been
+    /// reached at `curr_time`. Returns the tasks whose wake up time has been reached.
+    #[cfg(feature = "timer")]
+    pub fn expired_sleepers(&mut self, curr_time: u32) -> BooleanVector {
+        let mut tasks_mask = 0;
+        for tid in 0..MAX_TASKS {
+            if let Some(wake_time) = self.wake_times[tid] {
+                if curr_time.wrapping_sub(wake_time) as i32 >= 0 {
+                    self.wake_times[tid] = None;
+                    tasks_mask |= 1 << tid;
+                }
+            }
+        }
+        self.unblock_tasks(tasks_mask, BlockReason::Sleeping);
+        self.unblock_tasks(tasks_mask, BlockReason::SlotWait);
+        tasks_mask
+    }
+
+    /// Makes the task `tid` periodic, it is released every `period` ticks starting at `first_release`.
+    #[cfg(feature = "timer")]
+    pub fn set_period(
+        &mut self,
+        tid: usize,
+        period: u32,
+        first_release: u32,
+    ) -> Result<(), KernelError> {
+        if tid >= MAX_TASKS || self.task_control_blocks[tid].is_none() {
+            return Err(KernelError::NotFound);
+        }
+        if period == 0 {
+            return Err(KernelError::LimitExceeded);
+        }
+        self.periods[tid] = Some(period);
+        self.next_releases[tid] = first_release;
+        Ok(())
+    }
+
+    /// Returns the periodic tasks whose release time has been reached at `curr_time`. The next
+    /// release is computed from the previous release time rather than `curr_time`, hence the releases
+    /// do not drift. A task which is still active when released has missed its period.
+    #[cfg(feature = "timer")]
+    pub fn due_periodic(&mut self, curr_time: u32) -> BooleanVector {
+        let mut tasks_mask = 0;
+        for tid in 0..MAX_TASKS {
+            if let Some(period) = self.periods[tid] {
+                if curr_time.wrapping_sub(self.next_releases[tid]) as i32 >= 0 {
+                    self.next_releases[tid] = self.next_releases[tid].wrapping_add(period);
+                    if self.active_tasks & (1 << tid) > 0 {
+                        self.missed_periods[tid] += 1;
+                    }
+                    tasks_mask |= 1 << tid;
+                }
+            }
+        }
+        tasks_mask
+    }
+
+    /// Marks the currently running task as finished. If a reaper is set, the task becomes a zombie
+    /// and the reaper is released to clean it up, else the task is cleaned up right away.
+    /// Returns the tasks released.
+    pub fn exit(&mut self) -> BooleanVector {
+        let curr_tid = self.curr_tid;
+        self.active_tasks &= !(1 << curr_tid as u32);
+        match self.reaper {
+            Some(reaper) if reaper != curr_tid => {
+                self.zombie_tasks |= 1 << curr_tid;
+                self.release(1 << reaper);
+                1 << reaper
+            }
+            _ => self.cleanup(curr_tid),
+        }
+    }
+
+    /// Cleans up all the zombie tasks. Returns the tasks released.
+    pub fn reap(&mut self) -> BooleanVector {
+        let mut released = 0;
+        for tid in 0..MAX_TASKS {
+            if self.zombie_tasks & (1 << tid) > 0 {
+                released |= self.cleanup(tid);
+            }
+        }
+        self.zombie_tasks = 0;
+        released
+    }
+
+    /// Unblocks the tasks which joined on the exited task `tid` and releases its successors.
+    /// Returns the successors released.
+    fn cleanup(&mut self, tid: usize) -> BooleanVector {
+        self.unblock_tasks(self.joiners[tid], BlockReason::Join);
+        self.joiners[tid] = 0;
+        let successors = self.successors[tid];
+        self.release(successors);
+        successors
+    }
+
+    /// Frees the slot of the task `tid` so that a task can be created at its priority again. The task
+    /// must not be active nor waiting to be reaped, and the idle task cannot be destroyed.
+    pub fn destroy(&mut self, tid: usize) -> Result<(), KernelError> {
+        if tid >= MAX_TASKS || self.task_control_blocks[tid].is_none() {
+            return Err(KernelError::NotFound);
+        }
+        let tid_mask = 1 << tid;
+        if (self.has_idle && tid == 0) || (self.active_tasks | self.zombie_tasks) & tid_mask > 0 {
+            return Err(KernelError::AccessDenied);
+        }
+        self.task_control_blocks[tid] = None;
+        for mask in self.block_masks.iter_mut() {
+            *mask &= !tid_mask;
+        }
+        self.blocked_tasks &= !tid_mask;
+        self.block_reasons[tid] = None;
+        #[cfg(feature = "timer")]
+        self.wake_slot_waiters(tid);
+        self.preempted_tasks &= !tid_mask;
+        self.task_names[tid] = "";
+        self.successors[tid] = 0;
+        // A task created later in the slot must not be released or woken on behalf of this one.
+        for other in 0..MAX_TASKS {
+            self.successors[other] &= !tid_mask;
+            self.joiners[other] &= !tid_mask;
+        }
+        self.inherited_priorities[tid] = None;
+        #[cfg(feature = "timer")]
+        {
+            self.release_times[tid] = None;
+            self.wake_times[tid] = None;
+            self.periods[tid] = None;
+            self.missed_periods[tid] = 0;
+        }
+        #[cfg(feature = "metrics")]
+        {
+            self.dispatch_counts[tid] = 0;
+        }
+        #[cfg(feature = "task_monitor")]
+        clear_deadline(tid as TaskId);
+        if self.reaper == Some(tid) {
+            self.reaper = None;
+        }
+        Ok(())
+    }
+
+    /// Unblocks the tasks waiting for the slot `tid` to be freed.
+    #[cfg(feature = "timer")]
+    fn wake_slot_waiters(&mut self, tid: usize) {
+        let waiters = core::mem::replace(&mut self.slot_waiters[tid], 0);
+        for waiter in 0..MAX_TASKS {
+            if waiters & (1 << waiter) > 0 {
+                self.wake_times[waiter] = None;
+            }
+        }
+        self.unblock_tasks(waiters, BlockReason::SlotWait);
     }
 
-    /// Removes `tasks_mask` from `blocked_tasks`.
-    pub fn unblock_tasks(&mut self, tasks_mask: BooleanVector) {
-        self.blocked_tasks &= !tasks_mask;
+    /// Sets the task `tid` as the reaper, which is released to clean up after exited tasks.
+    pub fn set_reaper(&mut self, tid: usize) -> Result<(), KernelError> {
+        if tid >= MAX_TASKS {
+            return Err(KernelError::LimitExceeded);
+        }
+        if tid == 0 {
+            return Err(KernelError::AccessDenied);
+        }
+        self.validate_tasks(1 << tid)?;
+        self.reaper = Some(tid);
+        Ok(())
+    }
+
+    /// Sets the tasks released when the task `tid` exits. Successor configurations which would form
+    /// a cycle, including a task succeeding itself, are rejected.
+    pub fn set_successors(&mut self, tid: usize, tasks_mask: BooleanVector) -> Result<(), KernelError> {
+        if tid >= MAX_TASKS {
+            return Err(KernelError::NotFound);
+        }
+        self.validate_tasks(tasks_mask)?;
+        let mut reachable = tasks_mask;
+        loop {
+            let mut next = reachable;
+            for i in 0..MAX_TASKS {
+                if reachable & (1 << i) > 0 {
+                    next |= self.successors[i];
+                }
+            }
+            if next == reachable {
+                break;
+            }
+            reachable = next;
+        }
+        if reachable & (1 << tid) > 0 {
+            return Err(KernelError::Cyclic);
+        }
+        self.successors[tid] = tasks_mask;
+        Ok(())
     }
 
     /// Returns the TaskId currently high priority task, which is in ready state.
     /// The highest priority is determined by calculating the most significant bit of boolean vector
     /// corresponding to the tasks in the ready state. The tasks in the ready state can be identified
     /// by the boolean and of `active_tasks` and boolean not(`blocked_tasks`).
-    pub fn get_next_tid(&self) -> usize {
+    /// A ready task preempts the currently running task only if its priority is higher than the
+    /// preemption threshold of the running task.
+    /// Returns `None` if no task is ready, which can only happen without the idle task.
+    pub fn get_next_tid(&self) -> Option<usize> {
+        self.next_tid_excluding(0)
+    }
+
+    /// Same as `get_next_tid`, but the tasks in `excluded` are not considered ready.
+    fn next_tid_excluding(&self, excluded: BooleanVector) -> Option<usize> {
+        if self.maintenance && self.started {
+            return Some(self.curr_tid);
+        }
+        let mask = self.active_tasks & !self.blocked_tasks & !self.migrated_tasks & !excluded;
+        debug_assert!(!self.has_idle || mask & 1 == 1, "idle task is not ready");
+        #[cfg(feature = "edf")]
+        return self.edf_next_tid(mask);
+        #[cfg(not(feature = "edf"))]
+        return self.priority_next_tid(mask);
+    }
+
+    /// Picks the ready task with the highest effective priority, see `get_next_tid`.
+    #[cfg(not(feature = "edf"))]
+    fn priority_next_tid(&self, mask: BooleanVector) -> Option<usize> {
+        let mut next_tid = get_msb(mask)?;
+        debug_assert!(next_tid < MAX_TASKS, "ready task out of bounds");
+        debug_assert!(self.curr_tid < MAX_TASKS, "current task out of bounds");
+        // only a lower priority task can have inherited a priority above that of `next_tid`.
+        let mut next_priority = self.effective_priority(next_tid);
+        for tid in 0..next_tid {
+            if mask & (1 << tid) > 0 && self.effective_priority(tid) > next_priority {
+                next_tid = tid;
+                next_priority = self.effective_priority(tid);
+            }
+        }
+        // within a band, the task whose turn it is runs regardless of the preemption thresholds.
+        #[cfg(feature = "round_robin")]
+        {
+            let turn = self.band_turns[self.bands[next_tid]];
+            if self.bands[turn] == self.bands[next_tid] && mask & (1 << turn) > 0 {
+                if mask & (1 << self.curr_tid) > 0 && self.bands[self.curr_tid] == self.bands[turn] {
+                    return Some(turn);
+                }
+                next_tid = turn;
+            }
+        }
+        if mask & (1 << self.curr_tid) > 0 {
+            if let Some(curr_tcb) = self.task_control_blocks[self.curr_tid] {
+                let threshold = self.effective_priority(self.curr_tid).max(curr_tcb.preempt_threshold);
+                if next_priority <= threshold {
+                    return Some(self.curr_tid);
+                }
+                // further preemptions are deferred until the preempted tasks resume.
+                if self.preempted_tasks.count_ones() as usize >= MAX_PREEMPT_NESTING {
+                    return Some(self.curr_tid);
+                }
+            }
+        }
+        return Some(next_tid);
+    }
+
+    /// Every task in a band of its own.
+    #[cfg(feature = "round_robin")]
+    const fn own_bands() -> [usize; MAX_TASKS] {
+        let mut bands = [0; MAX_TASKS];
+        let mut tid = 0;
+        while tid < MAX_TASKS {
+            bands[tid] = tid;
+            tid += 1;
+        }
+        bands
+    }
+
+    /// Makes the tasks `first` to `last` a round-robin band, which share the priority of `last`.
+    #[cfg(feature = "round_robin")]
+    pub fn set_band(&mut self, first: usize, last: usize) -> Result<(), KernelError> {
+        if first > last || last >= MAX_TASKS {
+            return Err(KernelError::NotFound);
+        }
+        if self.has_idle && first == 0 {
+            return Err(KernelError::AccessDenied);
+        }
+        for tid in first..=last {
+            self.bands[tid] = last;
+        }
+        self.band_turns[last] = last;
+        Ok(())
+    }
+
+    /// Returns true if the slot `tid` belongs to a band of more than one task.
+    #[cfg(feature = "round_robin")]
+    fn in_band(&self, tid: usize) -> bool {
+        (0..MAX_TASKS).any(|other| other != tid && self.bands[other] == self.bands[tid])
+    }
+
+    /// Charges a tick to the quantum of the running task. Once the quantum is used up, the turn of
+    /// its band passes to the next ready task of the band, returns true if the turn has passed.
+    #[cfg(feature = "round_robin")]
+    pub fn round_robin_tick(&mut self) -> bool {
+        self.slice_used += 1;
+        if self.slice_used < RR_QUANTUM {
+            return false;
+        }
+        self.slice_used = 0;
+        let curr_tid = self.curr_tid;
+        let band = self.bands[curr_tid];
         let mask = self.active_tasks & !self.blocked_tasks & !self.migrated_tasks;
-        return get_msb(mask).unwrap();
+        for i in 1..MAX_TASKS {
+            let tid = (curr_tid + i) % MAX_TASKS;
+            if self.bands[tid] == band && mask & (1 << tid) > 0 {
+                self.band_turns[band] = tid;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the absolute deadline of the current release of the task `tid`, `None` if it has not
+    /// been released.
+    #[cfg(feature = "edf")]
+    fn abs_deadline(&self, tid: usize) -> Option<u32> {
+        let tcb = self.task_control_blocks[tid]?;
+        self.release_times[tid].map(|release_time| release_time.wrapping_add(tcb.deadline))
     }
 
-    /// Updates `active_tasks` with `task_mask`.
+    /// Returns true if the task `a` is to run before the task `b` under EDF: its deadline is earlier,
+    /// a task without a deadline comes last, and ties are broken by the higher priority.
+    #[cfg(feature = "edf")]
+    fn edf_before(&self, a: usize, b: usize) -> bool {
+        match (self.abs_deadline(a), self.abs_deadline(b)) {
+            (Some(da), Some(db)) if da != db => (da.wrapping_sub(db) as i32) < 0,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            _ => a > b,
+        }
+    }
+
+    /// Picks the ready task with the earliest absolute deadline, the idle task runs only if no other
+    /// task is ready. The running task is only preempted by a task with a strictly earlier deadline,
+    /// preemption thresholds are not taken into account.
+    #[cfg(feature = "edf")]
+    fn edf_next_tid(&self, mask: BooleanVector) -> Option<usize> {
+        let idle_mask = if self.has_idle { 1 } else { 0 };
+        let tasks_mask = mask & !idle_mask;
+        let mut next_tid = match get_msb(tasks_mask) {
+            Some(tid) => tid,
+            None => return get_msb(mask),
+        };
+        for tid in 0..MAX_TASKS {
+            if tasks_mask & (1 << tid) > 0 && self.edf_before(tid, next_tid) {
+                next_tid = tid;
+            }
+        }
+        let curr_tid = self.curr_tid;
+        if tasks_mask & (1 << curr_tid) > 0 && next_tid != curr_tid {
+            let curr_deadline = self.abs_deadline(curr_tid);
+            if curr_deadline.is_some() && curr_deadline == self.abs_deadline(next_tid) {
+                return Some(curr_tid);
+            }
+        }
+        Some(next_tid)
+    }
+
+    /// Returns the priority the task `tid` is scheduled with, which is the higher of its own priority
+    /// and the priority it has inherited.
+    pub fn effective_priority(&self, tid: usize) -> usize {
+        match self.inherited_priorities[tid] {
+            Some(priority) if priority > tid => priority,
+            _ => tid,
+        }
+    }
+
+    /// Sets the priority inherited by the task `tid` and returns the one it replaces.
+    pub fn set_inherited_priority(&mut self, tid: usize, priority: Option<usize>) -> Option<usize> {
+        core::mem::replace(&mut self.inherited_priorities[tid], priority)
+    }
+
+    /// Returns true if the idle task is running and no other task is ready, in which case
+    /// scheduling cannot switch to another task.
+    pub fn is_idle_only(&self) -> bool {
+        let mask = self.active_tasks & !self.blocked_tasks & !self.migrated_tasks;
+        self.has_idle && self.started && self.curr_tid == 0 && mask == 1
+    }
+
+    /// Returns the task which would preempt the currently running task if the scheduler ran now,
+    /// `None` if the currently running task would keep running.
+    pub fn pending_preemption(&self) -> Option<usize> {
+        let next_tid = self.get_next_tid()?;
+        if next_tid == self.curr_tid {
+            None
+        } else {
+            Some(next_tid)
+        }
+    }
+
+    /// Decides the next task to run and updates `curr_tid` and `started`, without touching the CPU
+    /// context. If a context switch is required, returns the task whose context has to be saved
+    /// (`None` if the scheduler had not started yet) and the task whose context has to be loaded.
+    /// A ready task without a TCB, i.e. released before it was created, cannot be dispatched. It is
+    /// reported as a `DispatchError` and skipped for this dispatch only: its release stays pending
+    /// until the task is created. The next ready task is picked instead, which is the idle task at
+    /// the latest. Without the idle task, no context switch happens if no ready task has a TCB, the
+    /// running task keeps the CPU.
+    pub fn switch_context(&mut self) -> Option<(Option<usize>, usize)> {
+        let curr_tid = self.curr_tid;
+        let mut skipped = 0;
+        let mut next_tid = self.get_next_tid()?;
+        // every pass skips another task, hence the loop ends within `MAX_TASKS` passes.
+        while self.task_control_blocks[next_tid].is_none() {
+            #[cfg(feature = "system_logger")]
+            logging::report(LogEventType::DispatchError(next_tid as TaskId));
+            skipped |= 1 << next_tid;
+            next_tid = self.next_tid_excluding(skipped)?;
+        }
+        if curr_tid == next_tid && self.started {
+            return None;
+        }
+        let prev_tid = if self.started {
+            let idle_mask = if self.has_idle { 1 } else { 0 };
+            let ready_tasks = self.active_tasks & !self.blocked_tasks & !idle_mask;
+            self.preempted_tasks |= ready_tasks & (1 << curr_tid);
+            Some(curr_tid)
+        } else {
+            self.started = true;
+            None
+        };
+        self.preempted_tasks &= !(1 << next_tid);
+        // a task which blocked or was preempted gives up what is left of its quantum.
+        #[cfg(feature = "round_robin")]
+        {
+            self.slice_used = 0;
+        }
+        #[cfg(feature = "metrics")]
+        {
+            self.dispatch_counts[next_tid] = self.dispatch_counts[next_tid].wrapping_add(1);
+        }
+        self.curr_tid = next_tid;
+        Some((prev_tid, next_tid))
+    }
+
+    /// Sets the preemption threshold of the task `tid`. The threshold cannot be lower than the
+    /// priority of the task. The threshold has no effect with the `edf` feature.
+    pub fn set_preempt_threshold(&mut self, tid: usize, threshold: usize) -> Result<(), KernelError> {
+        if threshold < tid || threshold >= MAX_TASKS {
+            return Err(KernelError::LimitExceeded);
+        }
+        let tcb = self
+            .task_control_blocks
+            .get_mut(tid)
+            .and_then(|tcb| tcb.as_mut())
+            .ok_or(KernelError::NotFound)?;
+        tcb.preempt_threshold = threshold;
+        Ok(())
+    }
+
+    /// Returns the preemption threshold of the task `tid`.
+    pub fn get_preempt_threshold(&self, tid: usize) -> Result<usize, KernelError> {
+        self.task_control_blocks
+            .get(tid)
+            .and_then(|tcb| tcb.as_ref())
+            .map(|tcb| tcb.preempt_threshold)
+            .ok_or(KernelError::NotFound)
+    }
+
+    /// Moves the task at priority `from` to the free priority `to`, along with its state and its bits
+    /// in the task masks of the scheduler. Task masks held outside the scheduler, like those of
+    /// resources and semaphores, are not updated. The preemption threshold of the task is raised to
+    /// `to` if it is lower.
+    pub fn change_priority(&mut self, from: usize, to: usize) -> Result<(), KernelError> {
+        if from >= MAX_TASKS || to >= MAX_TASKS || self.task_control_blocks[from].is_none() {
+            return Err(KernelError::NotFound);
+        }
+        if self.has_idle && (from == 0 || to == 0) {
+            return Err(KernelError::AccessDenied);
+        }
+        if self.task_control_blocks[to].is_some() {
+            return Err(KernelError::Exists);
+        }
+        // the tasks of a band are contiguous priorities, a task cannot be moved in or out of one.
+        #[cfg(feature = "round_robin")]
+        {
+            if self.in_band(from) || self.in_band(to) {
+                return Err(KernelError::AccessDenied);
+            }
+        }
+        let mut tcb = self.task_control_blocks[from].take().unwrap();
+        if tcb.preempt_threshold < to {
+            tcb.preempt_threshold = to;
+        }
+        self.task_control_blocks[to] = Some(tcb);
+        self.joiners.swap(from, to);
+        #[cfg(feature = "timer")]
+        {
+            self.release_times.swap(from, to);
+            self.wake_times.swap(from, to);
+            self.periods.swap(from, to);
+            self.next_releases.swap(from, to);
+            self.missed_periods.swap(from, to);
+        }
+        self.task_names.swap(from, to);
+        self.successors.swap(from, to);
+        self.inherited_priorities.swap(from, to);
+        self.block_reasons.swap(from, to);
+        self.preempted_tasks = move_bit(self.preempted_tasks, from, to);
+        #[cfg(feature = "metrics")]
+        self.dispatch_counts.swap(from, to);
+        #[cfg(feature = "task_monitor")]
+        move_task(from as TaskId, to as TaskId);
+
+        self.active_tasks = move_bit(self.active_tasks, from, to);
+        self.blocked_tasks = move_bit(self.blocked_tasks, from, to);
+        for mask in self.block_masks.iter_mut() {
+            *mask = move_bit(*mask, from, to);
+        }
+        self.migrated_tasks = move_bit(self.migrated_tasks, from, to);
+        self.zombie_tasks = move_bit(self.zombie_tasks, from, to);
+        for tid in 0..MAX_TASKS {
+            self.joiners[tid] = move_bit(self.joiners[tid], from, to);
+            self.successors[tid] = move_bit(self.successors[tid], from, to);
+        }
+        #[cfg(feature = "timer")]
+        {
+            for tid in 0..MAX_TASKS {
+                self.slot_waiters[tid] = move_bit(self.slot_waiters[tid], from, to);
+            }
+            // the slot `from` is free now.
+            self.wake_slot_waiters(from);
+        }
+        if self.reaper == Some(from) {
+            self.reaper = Some(to);
+        }
+        if self.curr_tid == from {
+            self.curr_tid = to;
+        }
+        Ok(())
+    }
+
+    /// Updates `active_tasks` with `task_mask`. A task may be released before it is created, its
+    /// deadline is then only set on its next release.
     pub fn release(&mut self, tasks_mask: BooleanVector) {
         #[cfg(feature = "task_monitor")]
         {
-            for i in 0..32 {
+            debug_assert!(
+                tasks_mask.checked_shr(MAX_TASKS as u32).unwrap_or(0) == 0,
+                "released task out of bounds"
+            );
+            for i in 0..MAX_TASKS {
                 if (tasks_mask & 1 << i) > 0 {
-                    set_deadline(i as TaskId, self.task_control_blocks[i].unwrap().deadline)
+                    if let Some(tcb) = self.task_control_blocks[i] {
+                        set_deadline(i as TaskId, tcb.deadline)
+                    }
                 }
             }
         }
         self.active_tasks |= tasks_mask;
     }
+
+    /// Replaces `active_tasks` with `tasks_mask`. The idle task is always kept active. Returns the
+    /// tasks which were newly released.
+    pub fn set_active_tasks(&mut self, tasks_mask: BooleanVector) -> BooleanVector {
+        let tasks_mask = if self.has_idle { tasks_mask | 1 } else { tasks_mask };
+        let released = tasks_mask & !self.active_tasks;
+        self.active_tasks &= tasks_mask;
+        self.release(released);
+        released
+    }
+
+    /// Records `curr_time` as the release time of all tasks in `tasks_mask`.
+    #[cfg(feature = "timer")]
+    pub fn stamp_release(&mut self, tasks_mask: BooleanVector, curr_time: u32) {
+        for tid in 0..MAX_TASKS {
+            if tasks_mask & (1 << tid) > 0 {
+                self.release_times[tid] = Some(curr_time);
+            }
+        }
+    }
+
+    /// Returns the subset of `tasks_mask` whose last release happened at least `min_interval`
+    /// ticks before `curr_time`. Tasks which were never released are always included.
+    #[cfg(feature = "timer")]
+    pub fn rate_limit(
+        &self,
+        tasks_mask: BooleanVector,
+        min_interval: u32,
+        curr_time: u32,
+    ) -> BooleanVector {
+        let mut allowed = 0;
+        for tid in 0..MAX_TASKS {
+            if tasks_mask & (1 << tid) == 0 {
+                continue;
+            }
+            match self.release_times[tid] {
+                Some(last) if curr_time.wrapping_sub(last) < min_interval => {}
+                _ => allowed |= 1 << tid,
+            }
+        }
+        allowed
+    }
+}
+
+/// Moves the bit `from` of `mask` to the bit `to`, which is expected to be clear.
+fn move_bit(mask: BooleanVector, from: usize, to: usize) -> BooleanVector {
+    if mask & (1 << from) > 0 {
+        (mask & !(1 << from)) | (1 << to)
+    } else {
+        mask
+    }
 }
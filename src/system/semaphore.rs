@@ -2,9 +2,11 @@
 //!
 use crate::kernel::tasks::{get_curr_tid, release, schedule};
 use crate::system::scheduler::{BooleanVector, Scheduler};
-use crate::utils::arch::{critical_section, Mutex};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
 use crate::KernelError;
 use core::cell::RefCell;
+use core::ptr;
 
 #[cfg(feature = "system_logger")]
 use {crate::kernel::logging, crate::system::system_logger::LogEventType};
@@ -18,13 +20,13 @@ pub struct Semaphore {
 
     /// A reference to access the kernel functions
     // TODO: remove this static lifetime? use Arc?
-    task_manager: &'static Mutex<RefCell<Scheduler>>,
+    task_manager: &'static KernelCell<Scheduler>,
 }
 
 impl Semaphore {
     /// Initializes a new semaphore instance.
     pub const fn new(
-        task_manager: &'static Mutex<RefCell<Scheduler>>,
+        task_manager: &'static KernelCell<Scheduler>,
         tasks: BooleanVector,
     ) -> Self {
         Self {
@@ -36,6 +38,30 @@ impl Semaphore {
 
     /// Signals the semaphore, all tasks specified in semaphore::flags can test for it and all tasks in semaphore::tasks are released
     pub fn signal_and_release(&'static self, tasks_mask: BooleanVector) {
+        self.signal(tasks_mask);
+        schedule(self.task_manager);
+    }
+
+    /// Signals all the semaphores in `batch` with the corresponding tasks mask under a single
+    /// critical section, and then schedules only once.
+    pub fn signal_and_release_batch(batch: &[(&'static Semaphore, BooleanVector)]) {
+        critical_section(|_| {
+            for (semaphore, tasks_mask) in batch {
+                semaphore.signal(*tasks_mask);
+            }
+        });
+        for (i, (semaphore, _)) in batch.iter().enumerate() {
+            let is_scheduled = batch[..i]
+                .iter()
+                .any(|(prev, _)| ptr::eq(prev.task_manager, semaphore.task_manager));
+            if !is_scheduled {
+                schedule(semaphore.task_manager);
+            }
+        }
+    }
+
+    /// Sets `tasks_mask` onto the flags and releases the tasks of the semaphore without scheduling.
+    fn signal(&self, tasks_mask: BooleanVector) {
         critical_section(|_| {
             let flags: &mut BooleanVector = &mut self.flags.borrow_mut();
             *flags |= tasks_mask;
@@ -47,7 +73,17 @@ impl Semaphore {
                 }
             }
         });
-        schedule(self.task_manager);
+    }
+
+    /// Returns the boolean vector of tasks released by the semaphore on being signaled.
+    pub fn targets(&self) -> BooleanVector {
+        self.tasks
+    }
+
+    /// Returns the boolean vector of tasks which have been signaled and have not yet consumed the
+    /// signal with `test_and_reset`.
+    pub fn waiters(&self) -> BooleanVector {
+        critical_section(|_| *self.flags.borrow())
     }
 
     /// Checks if the flag was enabled for the currently running task.
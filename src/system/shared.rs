@@ -1,7 +1,8 @@
 use crate::kernel::tasks::{TaskManager, TaskManager_C1, schedule};
 use crate::system::resource::{PiStackGlobal, PiStackGlobal_C1, Resource};
 use crate::system::scheduler::{BooleanVector, Scheduler};
-use crate::utils::arch::{critical_section, Mutex};
+use crate::utils::arch::critical_section;
+use crate::utils::kernel_cell::KernelCell;
 use crate::system::spinlock::{spinlock, spinlock_try, spinunlock, TASKMANAGER_LOCK};
 use crate::KernelError;
 
@@ -23,7 +24,7 @@ pub struct Shared<'a, T: Sized> {
     lock_ref: &'a AtomicBool,
     curr_tid_ref: &'a RefCell<usize>,
     other_resource_taskmask: BooleanVector,
-    other_core_task_manager: &'static Mutex<RefCell<Scheduler>>,
+    other_core_task_manager: &'static KernelCell<Scheduler>,
 }
 
 impl<'a, T: Sized> Shared<'a, T> {
@@ -31,14 +32,14 @@ impl<'a, T: Sized> Shared<'a, T> {
         resource: Resource<T>,
         lock_ref: &'a AtomicBool,
         other_resource_taskmask: BooleanVector,
-        other_core_task_manager: &'static Mutex<RefCell<Scheduler>>,
+        other_core_task_manager: &'static KernelCell<Scheduler>,
         curr_tid_ref: &'a RefCell<usize>,
     ) -> Self {
         Shared { resource, lock_ref, other_resource_taskmask, other_core_task_manager, curr_tid_ref }
     }
 
     pub fn lock(&self) -> Result<&T, KernelError> {
-        let v = self.resource.lock()?;
+        let v = self.resource.lock_raw()?;
         // spin lock here
         // TODO: make a usable spinlock api
         while let Err(_) =
@@ -85,7 +86,7 @@ impl<'a, T: Sized> Shared<'a, T> {
 
     pub fn unlock(&self) -> Result<(), KernelError> {
         self.lock_ref.store(false, Ordering::SeqCst);
-        self.resource.unlock();
+        self.resource.unlock_raw();
         Ok(())
     }
 
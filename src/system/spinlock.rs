@@ -1,23 +1,91 @@
-use core::sync::atomic::{AtomicBool, Ordering};
+//! Spinlocks synchronizing access to the `TaskManager`s across cores. Without the `multicore`
+//! feature there is a single core, the critical sections suffice and the spinlocks compile to nothing.
+use core::sync::atomic::AtomicU8;
+#[cfg(feature = "multicore")]
+use core::sync::atomic::Ordering;
+#[cfg(feature = "multicore")]
+use crate::utils::arch::critical_section;
+#[cfg(feature = "multicore")]
+use crate::utils::kernel_cell::KernelCell;
 
-pub static TASKMANAGER_LOCK: AtomicBool = AtomicBool::new(false);
+/// Identifies a core.
+#[cfg(feature = "multicore")]
+pub type CoreId = u8;
 
-pub fn spinlock_try<'a>(lock: &'a AtomicBool) -> Result<bool, bool> {
-    lock.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+/// A spinlock which records the core holding it.
+pub struct SpinLock {
+    /// 0 if the lock is free, else the id of the holder core plus one.
+    #[cfg_attr(not(feature = "multicore"), allow(dead_code))]
+    state: AtomicU8,
 }
 
-#[inline(never)]
-pub fn spinlock<'a>(lock: &'a AtomicBool) {
-    while let Err(_) =
-        lock.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst) {
-            // do nothing
+impl SpinLock {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(0),
         }
+    }
+}
+
+pub static TASKMANAGER_LOCK: SpinLock = SpinLock::new();
+
+/// Returns the id of the core executing the code, set by the application as the kernel has no
+/// portable way of telling the cores apart.
+#[cfg(feature = "multicore")]
+static CORE_ID_SOURCE: KernelCell<Option<fn() -> CoreId>> = KernelCell::new(None);
+
+/// Sets the function which returns the id of the executing core, the id is recorded as the holder
+/// of a spinlock. Until set, every core is reported as core 0.
+#[cfg(feature = "multicore")]
+pub fn set_core_id_source(source: fn() -> CoreId) {
+    critical_section(|cs_token| {
+        *CORE_ID_SOURCE.borrow(cs_token).borrow_mut() = Some(source);
+    })
+}
+
+#[cfg(feature = "multicore")]
+fn core_id() -> CoreId {
+    critical_section(|cs_token| *CORE_ID_SOURCE.borrow(cs_token).borrow()).map_or(0, |source| source())
+}
+
+/// Returns the id of the core holding `lock`, `None` if the lock is free.
+#[cfg(feature = "multicore")]
+pub fn holder(lock: &SpinLock) -> Option<CoreId> {
+    match lock.state.load(Ordering::SeqCst) {
+        0 => None,
+        state => Some(state - 1),
+    }
+}
+
+#[cfg(feature = "multicore")]
+pub fn spinlock_try<'a>(lock: &'a SpinLock) -> Result<bool, bool> {
+    lock.state
+        .compare_exchange(0, core_id() + 1, Ordering::SeqCst, Ordering::SeqCst)
+        .map(|_| false)
+        .map_err(|_| true)
+}
+
+#[cfg(feature = "multicore")]
+#[inline(never)]
+pub fn spinlock<'a>(lock: &'a SpinLock) {
+    while let Err(_) = spinlock_try(lock) {
+        // do nothing
+    }
 }
 
+#[cfg(feature = "multicore")]
 #[inline(never)]
-pub fn spinunlock<'a>(lock: &'a AtomicBool) {
-    lock.store(false, Ordering::SeqCst);
+pub fn spinunlock<'a>(lock: &'a SpinLock) {
+    lock.state.store(0, Ordering::SeqCst);
 }
 
+#[cfg(not(feature = "multicore"))]
+#[inline(always)]
+pub fn spinlock<'a>(_lock: &'a SpinLock) {}
+
+#[cfg(not(feature = "multicore"))]
+#[inline(always)]
+pub fn spinunlock<'a>(_lock: &'a SpinLock) {}
+
 // TODO: refactor spinlock that accepts a closure. but this might increase the code size, is it
 // worth it?
@@ -1,11 +1,12 @@
 use crate::config::MAX_LOGS;
+use crate::system::ring_buffer::RingBuffer;
 use crate::system::scheduler::{BooleanVector, TaskId};
 use core::fmt;
 
 #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
 use crate::system::event::EventId;
 
-pub type Logs = [Option<LogEvent>; MAX_LOGS];
+pub type Logs = RingBuffer<LogEvent, MAX_LOGS>;
 
 #[derive(Clone, Copy)]
 pub enum LogEventType {
@@ -19,7 +20,15 @@ pub enum LogEventType {
     MessageRecieve(TaskId),
     SemaphoreSignal(BooleanVector, BooleanVector),
     SemaphoreReset(TaskId),
+    /// A unit was posted to a counting semaphore, carries the count after the post and the task
+    /// released.
+    SemaphorePost(u32, BooleanVector),
     DeadlineExpired(TaskId, u32),
+    DispatchError(TaskId),
+    /// A task inherited a priority, carries the task, its old and its new priority.
+    PriorityBoost(TaskId, u32, u32),
+    /// A task gave up an inherited priority, carries the task, its old and its new priority.
+    PriorityRestore(TaskId, u32, u32),
     #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
     TimerEvent(EventId),
 }
@@ -41,8 +50,6 @@ impl LogEvent {
 
 pub struct SystemLogger {
     logs: Logs,
-    start: usize,
-    end: usize,
     pub release_log: bool,
     pub block_tasks_log: bool,
     pub unblock_tasks_log: bool,
@@ -53,18 +60,16 @@ pub struct SystemLogger {
     pub message_recieve_log: bool,
     pub semaphore_signal_log: bool,
     pub semaphore_reset_log: bool,
+    pub priority_inheritance_log: bool,
 
     #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
     pub timer_event_log: bool,
 }
-// use a circular queue instead of this crap.
 // ensure the handler is not None in start_kernel.
 impl SystemLogger {
     pub const fn new() -> Self {
         Self {
-            logs: [None; MAX_LOGS],
-            start: 0,
-            end: 0,
+            logs: RingBuffer::new(),
             release_log: false,
             block_tasks_log: false,
             unblock_tasks_log: false,
@@ -75,31 +80,21 @@ impl SystemLogger {
             message_recieve_log: false,
             semaphore_signal_log: false,
             semaphore_reset_log: false,
+            priority_inheritance_log: false,
 
             #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
             timer_event_log: false,
         }
     }
+    /// Buffers `event`, the oldest event is discarded if the log is full.
     pub fn push(&mut self, event: LogEvent) {
-        self.logs[self.end] = Some(event);
-        self.end = (self.end + 1) % MAX_LOGS;
-        if self.start == self.end {
-            self.start = (self.start + 1) % MAX_LOGS;
-        }
+        self.logs.push_overwrite(event);
     }
     pub fn clear(&mut self) {
-        for val in self.logs.iter_mut() {
-            *val = None;
-        }
-        self.start = 0;
-        self.end = 0;
+        self.logs.clear();
     }
     pub fn pop(&mut self) -> Option<LogEvent> {
-        let pos = self.start;
-        let val = self.logs[pos];
-        self.logs[pos] = None;
-        self.start = (self.start + 1) % MAX_LOGS;
-        return val;
+        self.logs.pop()
     }
 }
 
@@ -118,7 +113,17 @@ impl fmt::Debug for LogEventType {
                 write!(f, "SemaphoreSignal")
             }
             LogEventType::SemaphoreReset(task_id) => write!(f, "SemaphoreReset"),
+            LogEventType::SemaphorePost(count, tasks_released) => {
+                write!(f, "SemaphorePost: count={}, released={}", count, tasks_released)
+            }
             LogEventType::DeadlineExpired(TaskId, u32) => write!(f, "DeadlineExpired"),
+            LogEventType::DispatchError(task_id) => write!(f, "DispatchError: {}", task_id),
+            LogEventType::PriorityBoost(task_id, old, new) => {
+                write!(f, "PriorityBoost: {} {} -> {}", task_id, old, new)
+            }
+            LogEventType::PriorityRestore(task_id, old, new) => {
+                write!(f, "PriorityRestore: {} {} -> {}", task_id, old, new)
+            }
             #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
             LogEventType::TimerEvent(EventId) => write!(f, "TimerEvent"),
         }
@@ -1,39 +1,124 @@
 use crate::config::MAX_TASKS;
 use crate::kernel::logging;
-use crate::system::scheduler::TaskId;
+use crate::system::scheduler::{BooleanVector, TaskId};
 use crate::system::system_logger::LogEventType;
 
+/// Criticality of the deadline of a task.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DeadlineKind {
+    /// A miss calls the deadline exceed handler.
+    Hard,
+    /// A miss is only counted and logged.
+    Soft,
+}
+
 pub struct TaskMonitor {
     active_deadlines: [Option<u32>; MAX_TASKS],
+    deadline_kinds: [DeadlineKind; MAX_TASKS],
+    /// Number of soft deadlines each task has missed.
+    soft_misses: [u32; MAX_TASKS],
     handler: Option<fn()>,
+    /// Declared worst-case execution time of each task in ticks.
+    wcets: [Option<u32>; MAX_TASKS],
+    /// Number of ticks each task has been running for since its last release.
+    runtimes: [u32; MAX_TASKS],
+    overrun_handler: Option<fn(TaskId)>,
+    miss_handler: Option<fn(TaskId)>,
 }
 
 impl TaskMonitor {
     pub const fn new() -> Self {
         Self {
             active_deadlines: [None; MAX_TASKS],
+            deadline_kinds: [DeadlineKind::Hard; MAX_TASKS],
+            soft_misses: [0; MAX_TASKS],
             handler: None,
+            wcets: [None; MAX_TASKS],
+            runtimes: [0; MAX_TASKS],
+            overrun_handler: None,
+            miss_handler: None,
         }
     }
+    /// Sets the deadline of a new release of the task, which also restarts its runtime accounting.
     pub fn set_deadline(&mut self, tid: TaskId, abs_deadline: u32) {
         self.active_deadlines[tid as usize] = Some(abs_deadline);
+        self.runtimes[tid as usize] = 0;
+    }
+    pub fn deadline(&self, tid: TaskId) -> Option<u32> {
+        self.active_deadlines[tid as usize]
+    }
+    pub fn set_deadline_kind(&mut self, tid: TaskId, kind: DeadlineKind) {
+        self.deadline_kinds[tid as usize] = kind;
+    }
+    pub fn soft_misses(&self, tid: TaskId) -> u32 {
+        self.soft_misses[tid as usize]
+    }
+    pub fn set_wcet(&mut self, tid: TaskId, wcet: u32) {
+        self.wcets[tid as usize] = Some(wcet);
+    }
+    pub fn set_overrun_handler(&mut self, handler: fn(TaskId)) {
+        self.overrun_handler = Some(handler);
+    }
+    /// Charges the current tick to the running task `tid`, the overrun handler is called once the
+    /// runtime of the task exceeds its WCET.
+    pub fn account_tick(&mut self, tid: TaskId) {
+        let runtime = &mut self.runtimes[tid as usize];
+        *runtime = runtime.saturating_add(1);
+        if let Some(wcet) = self.wcets[tid as usize] {
+            if *runtime == wcet + 1 {
+                if let Some(handler) = self.overrun_handler {
+                    handler(tid);
+                }
+            }
+        }
     }
     pub fn set_handler(&mut self, handler: fn()) {
         self.handler = Some(handler);
     }
+    pub fn set_miss_handler(&mut self, handler: fn(TaskId)) {
+        self.miss_handler = Some(handler);
+    }
+    pub fn miss_handler(&self) -> Option<fn(TaskId)> {
+        self.miss_handler
+    }
+    pub fn runtimes(&self) -> &[u32; MAX_TASKS] {
+        &self.runtimes
+    }
     pub fn clear_deadline(&mut self, tid: TaskId) {
         self.active_deadlines[tid as usize] = None;
     }
-    pub fn sweep_deadlines(&mut self, curr_time: u32) {
+    /// Moves the monitoring state of the task `from` to the task `to`.
+    pub fn move_task(&mut self, from: TaskId, to: TaskId) {
+        let (from, to) = (from as usize, to as usize);
+        self.active_deadlines.swap(from, to);
+        self.deadline_kinds.swap(from, to);
+        self.soft_misses.swap(from, to);
+        self.wcets.swap(from, to);
+        self.runtimes.swap(from, to);
+    }
+    /// Hard deadlines which expire at `curr_time` call the deadline exceed handler, soft ones are
+    /// counted and logged as `DeadlineExpired`. Returns the tasks which missed their deadline.
+    pub fn sweep_deadlines(&mut self, curr_time: u32) -> BooleanVector {
+        let mut missed = 0;
         for tid in 0..MAX_TASKS {
             if let Some(deadline) = self.active_deadlines[tid] {
                 if deadline == curr_time {
                     self.active_deadlines[tid] = None;
-                    if self.handler.is_some() {
-                        (self.handler.unwrap())();
+                    missed |= 1 << tid;
+                    match self.deadline_kinds[tid] {
+                        DeadlineKind::Hard => {
+                            if self.handler.is_some() {
+                                (self.handler.unwrap())();
+                            }
+                        }
+                        DeadlineKind::Soft => {
+                            self.soft_misses[tid] = self.soft_misses[tid].saturating_add(1);
+                            logging::report(LogEventType::DeadlineExpired(tid as TaskId, deadline));
+                        }
                     }
                 }
             }
         }
+        missed
     }
 }
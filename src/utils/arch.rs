@@ -4,35 +4,57 @@
 
 // Platform specific Exports
 // pub use cortex_m::interrupt::free as critical_section;
-pub use cortex_m::interrupt::{Mutex, CriticalSection, disable, enable};
+pub use cortex_m::interrupt::{Mutex, CriticalSection, enable};
+#[cfg(not(feature = "basepri"))]
+pub use cortex_m::interrupt::disable;
 pub use cortex_m::peripheral::syst::SystClkSource;
 pub use cortex_m::peripheral::Peripherals;
+#[cfg(feature = "metrics")]
+pub use cortex_m::peripheral::DWT;
+#[cfg(feature = "timer")]
+pub use cortex_m::peripheral::{SCB, SYST};
+use crate::config::MAX_TASKS;
+#[cfg(feature = "basepri")]
+use crate::config::KERNEL_BASEPRI;
 use crate::system::scheduler::*;
-use core::cell::RefCell;
+use crate::utils::kernel_cell::KernelCell;
+use crate::KernelError;
 
 use cortex_m::register::control;
 use cortex_m_rt::exception;
 
-use crate::kernel::tasks::{schedule, TaskManager, TaskManager_C1};
-use crate::system::spinlock::{spinlock, spinlock_try, spinunlock, TASKMANAGER_LOCK};
+use crate::kernel::tasks::{schedule, yield_task, TaskManager};
+#[cfg(feature = "timer")]
+use crate::kernel::tasks::isr_exit;
+#[cfg(feature = "round_robin")]
+use crate::kernel::tasks::round_robin_tick;
+#[cfg(feature = "multicore")]
+use crate::kernel::tasks::TaskManager_C1;
+use crate::system::spinlock::{spinlock, spinunlock, TASKMANAGER_LOCK};
 use crate::system::scheduler::TaskControlBlock;
+#[cfg(feature = "multicore")]
 use cortex_m_semihosting::hprintln;
 
 #[cfg(any(feature = "events_32", feature = "events_16", feature = "events_64"))]
 use crate::kernel::events::sweep_event_table;
 
 #[cfg(feature = "task_monitor")]
-use crate::kernel::task_monitor::sweep_deadlines;
+use crate::kernel::task_monitor::{account_tick, sweep_deadlines};
 
 #[cfg(feature = "timer")]
 use crate::kernel::timer::update_time;
 
+#[cfg(feature = "system_logger")]
+use crate::kernel::logging::process_idle_sink;
+
+#[cfg(not(feature = "basepri"))]
 #[inline(never)]
 unsafe fn enable_noinline(){
     enable();
 }
 
 /// this is replication of critical_section from cortex_m crate
+#[cfg(not(feature = "basepri"))]
 #[inline]
 pub fn critical_section<F, R>(f: F) -> R
 where
@@ -54,25 +76,45 @@ where
     r
 }
 
-/// Returns the MSB of `val`. It is written using CLZ instruction.
-pub fn get_msb(val: u32) -> Option<usize> {
-    let mut res: usize;
+/// Critical section which only masks the interrupts with a priority at or below `KERNEL_BASEPRI`,
+/// interrupts of higher priority still preempt the kernel. Such interrupts must not call into the
+/// kernel. Only available on ARMv7-M.
+#[cfg(feature = "basepri")]
+#[inline]
+pub fn critical_section<F, R>(f: F) -> R
+where
+    F: FnOnce(&CriticalSection) -> R,
+{
+    let basepri = cortex_m::register::basepri::read();
+
+    // raise the execution priority, only if it is lower than the kernel priority
+    cortex_m::register::basepri_max::write(KERNEL_BASEPRI);
+
+    let r = f(unsafe { &CriticalSection::new() });
+
+    unsafe { cortex_m::register::basepri::write(basepri) }
+
+    r
+}
+
+/// Sets the priorities of the exceptions the kernel runs in so that kernel critical sections mask
+/// them: SysTick and SVCall at `KERNEL_BASEPRI`, PendSV at the lowest priority so that the context
+/// switch runs once every other handler has returned.
+#[cfg(feature = "basepri")]
+pub fn set_kernel_priorities() {
+    // SHPR holds the priorities of the system handlers from MemManage (exception 4) onwards.
+    const SHPR_SVCALL: usize = 11 - 4;
+    const SHPR_PENDSV: usize = 14 - 4;
+    const SHPR_SYSTICK: usize = 15 - 4;
     unsafe {
-        asm!(
-            "clz {1}, {0}",
-            in(reg) val,
-            out(reg) res,
-        );
+        let scb = &*cortex_m::peripheral::SCB::ptr();
+        scb.shpr[SHPR_SVCALL].write(KERNEL_BASEPRI);
+        scb.shpr[SHPR_PENDSV].write(0xff);
+        scb.shpr[SHPR_SYSTICK].write(KERNEL_BASEPRI);
     }
-    res = 32 - res;
-    if res == 0 {
-        return None;
-    } else {
-        res -= 1;
-    }
-    return Some(res);
 }
 
+
 /// Creates an SVC Interrupt
 pub fn svc_call() {
     unsafe {
@@ -80,6 +122,106 @@ pub fn svc_call() {
     }
 }
 
+/// Creates an SVC Interrupt which yields the current task.
+pub fn svc_yield() {
+    unsafe {
+        asm!("svc 2");
+    }
+}
+
+/// Loads `pattern`, `pattern + 1`, ..., `pattern + 7` into r4 to r11, yields the current task with
+/// `svc 2` and, once the task resumes, returns the bits in which the registers differ from the
+/// values loaded. The registers of the caller are preserved.
+#[cfg(feature = "self_test")]
+pub fn yield_with_pattern(pattern: u32) -> u32 {
+    let mismatch: u32;
+    unsafe {
+        asm!(
+            "push {{r4-r11}}",
+            "mov r4, r0",
+            "add r5, r0, #1",
+            "add r6, r0, #2",
+            "add r7, r0, #3",
+            "add r8, r0, #4",
+            "add r9, r0, #5",
+            "add r10, r0, #6",
+            "add r11, r0, #7",
+            "svc 2",
+            "eor r1, r4, r0",
+            "add r2, r0, #1",
+            "eor r2, r2, r5",
+            "orr r1, r1, r2",
+            "add r2, r0, #2",
+            "eor r2, r2, r6",
+            "orr r1, r1, r2",
+            "add r2, r0, #3",
+            "eor r2, r2, r7",
+            "orr r1, r1, r2",
+            "add r2, r0, #4",
+            "eor r2, r2, r8",
+            "orr r1, r1, r2",
+            "add r2, r0, #5",
+            "eor r2, r2, r9",
+            "orr r1, r1, r2",
+            "add r2, r0, #6",
+            "eor r2, r2, r10",
+            "orr r1, r1, r2",
+            "add r2, r0, #7",
+            "eor r2, r2, r11",
+            "orr r0, r1, r2",
+            "pop {{r4-r11}}",
+            inout("r0") pattern => mismatch,
+            out("r1") _,
+            out("r2") _,
+        );
+    }
+    mismatch
+}
+
+/// Creates an SVC Interrupt which makes thread mode privileged.
+fn svc_privilege() {
+    unsafe {
+        asm!("svc 3");
+    }
+}
+
+/// Sets the privilege level of thread mode.
+fn set_thread_privilege(privileged: bool) {
+    let npriv: u32 = if privileged { 0 } else { 1 };
+    unsafe {
+        asm!(
+            "mrs {tmp}, CONTROL",
+            "bic {tmp}, {tmp}, #1",
+            "orr {tmp}, {tmp}, {npriv}",
+            "msr CONTROL, {tmp}",
+            "isb",
+            tmp = out(reg) _,
+            npriv = in(reg) npriv,
+        );
+    }
+}
+
+/// Runs `f` in privileged mode, so that an unprivileged task can perform a vetted privileged
+/// operation (like reconfiguring a peripheral) without being privileged for the rest of its
+/// execution. The task returns to unprivileged mode once `f` returns. Only the tasks allowed with
+/// `allow_privilege` can raise their privilege, `AccessDenied` is returned for every other task and
+/// `f` is not run. The privilege is part of the task context, hence `f` may be preempted.
+pub fn with_privilege<F, R>(f: F) -> Result<R, KernelError>
+where
+    F: FnOnce() -> R,
+{
+    if is_privileged() {
+        return Ok(f());
+    }
+    svc_privilege();
+    if !is_privileged() {
+        return Err(KernelError::AccessDenied);
+    }
+    let res = f();
+    set_thread_privilege(false);
+    Ok(res)
+}
+
 #[inline(always)]
 pub unsafe fn return_to_psp() {
     asm!(
@@ -143,8 +285,8 @@ pub fn load_context(task_stack: &TaskControlBlock) {
 /// ### SysTick Interrupt handler
 /// Its the Crux of the Kernel’s time management module and Task scheduling.
 /// This interrupt handler updates the time and also dispatches the appropriate event handlers.
-/// The interrupt handler also calls `schedule()` in here so as to dispatch any higher priority
-/// task if there are any.
+/// On exit, a context switch is pended if a task which should preempt the running task became
+/// ready, unless one of the sweeps has already pended it.
 
 #[cfg(feature = "timer")]
 #[exception]
@@ -156,10 +298,17 @@ fn SysTick() {
     update_time();
 
     #[cfg(feature = "task_monitor")]
-    sweep_deadlines();
+    {
+        sweep_deadlines();
+        account_tick(&TaskManager);
+    }
+
+    #[cfg(feature = "round_robin")]
+    round_robin_tick(&TaskManager);
 
-    // hprintln!("hello");
-    // schedule();
+    if !SCB::is_pendsv_pending() {
+        isr_exit(&TaskManager);
+    }
 }
 /// ### SVC Interrupt handler,
 /// calls `tasks::schedule()`
@@ -170,12 +319,64 @@ fn SysTick() {
 
 #[export_name = "SVCall_0"]
 pub extern "C" fn SVCall_0() {
-    schedule(&TaskManager);
+    dispatch_svc(&TaskManager);
 }
 
+#[cfg(feature = "multicore")]
 #[export_name = "SVCall_1"]
 pub extern "C" fn SVCall_1() {
-    schedule(&TaskManager_C1);
+    dispatch_svc(&TaskManager_C1);
+}
+
+/// The SVC immediate with which `svc_call` requests a reschedule.
+const SVC_SCHEDULE: u8 = 1;
+/// The SVC immediate with which `svc_yield` yields the current task.
+const SVC_YIELD: u8 = 2;
+/// The SVC immediate with which `with_privilege` makes thread mode privileged.
+const SVC_PRIVILEGE: u8 = 3;
+
+/// Handler for the SVC immediates which are not handled by the kernel.
+static SVC_FALLBACK: KernelCell<Option<fn(u8)>> = KernelCell::new(None);
+
+pub fn set_svc_fallback(handler: fn(u8)) {
+    critical_section(|cs_token| {
+        *SVC_FALLBACK.borrow(cs_token).borrow_mut() = Some(handler);
+    })
+}
+
+/// Returns the immediate of the SVC instruction which raised the exception. SVCs are only issued
+/// by tasks, hence the exception frame is on the process stack.
+fn svc_immediate() -> u8 {
+    unsafe {
+        let frame = cortex_m::register::psp::read() as *const u32;
+        let pc = *frame.add(6);
+        *((pc - 2) as *const u8)
+    }
+}
+
+/// Schedules on `SVC_SCHEDULE`, yields on `SVC_YIELD` and raises the privilege of thread mode on
+/// `SVC_PRIVILEGE` if the running task may do so, every other immediate is passed on to the SVC
+/// fallback handler.
+fn dispatch_svc(task_manager: &'static KernelCell<Scheduler>) {
+    match svc_immediate() {
+        SVC_SCHEDULE => schedule(task_manager),
+        SVC_YIELD => yield_task(task_manager),
+        SVC_PRIVILEGE => {
+            let may_elevate = critical_section(|cs_token| {
+                spinlock(&TASKMANAGER_LOCK);
+                let may_elevate = task_manager.borrow(cs_token).borrow().may_elevate();
+                spinunlock(&TASKMANAGER_LOCK);
+                may_elevate
+            });
+            if may_elevate {
+                set_thread_privilege(true);
+            }
+        }
+        imm => match critical_section(|cs_token| *SVC_FALLBACK.borrow(cs_token).borrow()) {
+            Some(handler) => handler(imm),
+            None => debug_assert!(false, "unhandled svc {}", imm),
+        },
+    }
 }
 
 /// ### PendSV Interrupt handler,
@@ -202,10 +403,13 @@ pub extern "C" fn SVCall_1() {
 //     unsafe { return_to_psp() }
 // }
 
+#[cfg(feature = "multicore")]
 #[inline(never)]
-fn get_next_tcb(t1: &'static Mutex<RefCell<Scheduler>>, t2: &'static Mutex<RefCell<Scheduler>>, cs_token: &CriticalSection) -> Option<TaskControlBlock>{
+fn get_next_tcb(t1: &'static KernelCell<Scheduler>, t2: &'static KernelCell<Scheduler>, cs_token: &CriticalSection) -> Option<TaskControlBlock>{
     let handler = &mut t1.borrow(cs_token).borrow_mut();
     let curr_tid: usize = handler.curr_tid;
+    debug_assert!(curr_tid < MAX_TASKS, "current task out of bounds");
+    debug_assert!(handler.migrated_tid < MAX_TASKS, "migrated task out of bounds");
     if handler.migrated_tid > 0 {
         let mut oc_handler = &mut t2.borrow(cs_token).borrow_mut();
         if handler.running_migrated {
@@ -229,20 +433,45 @@ fn get_next_tcb(t1: &'static Mutex<RefCell<Scheduler>>, t2: &'static Mutex<RefCe
             return migrate_task;
         }
     } else {
-        let next_tid: usize = handler.get_next_tid() as usize;
-        if curr_tid != next_tid || (!handler.started) {
-            if handler.started {
-                let curr_task = handler.task_control_blocks[curr_tid].as_ref().unwrap();
-                curr_task.save_context();
-            } else {
-                handler.started = true;
+        return switch_tcb(handler, cs_token);
+    }
+}
+
+#[cfg(not(feature = "multicore"))]
+#[inline(never)]
+fn get_next_tcb(t1: &'static KernelCell<Scheduler>, cs_token: &CriticalSection) -> Option<TaskControlBlock>{
+    let handler = &mut t1.borrow(cs_token).borrow_mut();
+    debug_assert!(handler.curr_tid < MAX_TASKS, "current task out of bounds");
+    switch_tcb(handler, cs_token)
+}
+
+/// Saves the context of the outgoing task and returns the TCB of the task to be loaded, if a
+/// context switch is required. The privilege of thread mode is saved and restored along with the
+/// context, so that a task preempted within `with_privilege` does not pass its privilege on.
+#[inline(always)]
+#[allow(unused_variables)]
+fn switch_tcb(handler: &mut Scheduler, cs_token: &CriticalSection) -> Option<TaskControlBlock> {
+    let privileged = is_privileged();
+    if !handler.started {
+        handler.thread_privileged = privileged;
+    }
+    let (prev_tid, next_tid) = handler.switch_context()?;
+    if let Some(prev_tid) = prev_tid {
+        let curr_task = handler.task_control_blocks[prev_tid].as_mut().unwrap();
+        curr_task.save_privilege(privileged);
+        curr_task.save_context();
+        #[cfg(feature = "stack_check")]
+        {
+            if !curr_task.is_stack_intact() {
+                stack_corrupted(prev_tid as TaskId, cs_token);
             }
-            handler.curr_tid = next_tid;
-            let next_tcb = handler.task_control_blocks[next_tid];
-            return next_tcb;
         }
     }
-    None
+    let next_task = handler.task_control_blocks[next_tid];
+    if let Some(ref next_task) = next_task {
+        set_thread_privilege(next_task.privilege(handler.thread_privileged));
+    }
+    next_task
 }
 
 #[export_name = "PendSV_0"]
@@ -294,7 +523,11 @@ pub extern "C" fn PendSV_0() {
         //     let handler = TaskManager.borrow(cs_token).borrow();
         //     handler.task_control_blocks[handler.curr_tid]
         // };
-        if let Some(ref next_task) = get_next_tcb(&TaskManager, &TaskManager_C1, cs_token) {
+        #[cfg(feature = "multicore")]
+        let next_task = get_next_tcb(&TaskManager, &TaskManager_C1, cs_token);
+        #[cfg(not(feature = "multicore"))]
+        let next_task = get_next_tcb(&TaskManager, cs_token);
+        if let Some(ref next_task) = next_task {
             next_task.load_context();
         }
         spinunlock(&TASKMANAGER_LOCK);
@@ -302,6 +535,7 @@ pub extern "C" fn PendSV_0() {
     unsafe { return_to_psp() }
 }
 
+#[cfg(feature = "multicore")]
 #[export_name = "PendSV_1"]
 pub extern "C" fn PendSV_1() {
     critical_section(|cs_token| {
@@ -360,8 +594,132 @@ pub fn set_pendsv() {
     // unsafe {asm!("isb")};
 }
 
+/// Handler called when the stack canary of a task is found overwritten on a context switch.
+#[cfg(feature = "stack_check")]
+static STACK_CORRUPTION_HANDLER: KernelCell<Option<fn(TaskId)>> = KernelCell::new(None);
+
+#[cfg(feature = "stack_check")]
+pub fn set_stack_corruption_handler(handler: fn(TaskId)) {
+    critical_section(|cs_token| {
+        *STACK_CORRUPTION_HANDLER.borrow(cs_token).borrow_mut() = Some(handler);
+    })
+}
+
+/// Calls the stack corruption handler for the task `tid`, panics if no handler is set.
+#[cfg(feature = "stack_check")]
+fn stack_corrupted(tid: TaskId, cs_token: &CriticalSection) {
+    match *STACK_CORRUPTION_HANDLER.borrow(cs_token).borrow() {
+        Some(handler) => handler(tid),
+        None => panic!("stack of task {} overflowed", tid),
+    }
+}
+
+/// Hook called right before the CPU sleeps in `wait_for_interrupt` or `wait_for_event`, the CPU
+/// sleeps only if it returns true.
+static PRE_SLEEP_HOOK: KernelCell<Option<fn() -> bool>> = KernelCell::new(None);
+
+pub fn set_pre_sleep_hook(hook: fn() -> bool) {
+    critical_section(|cs_token| {
+        *PRE_SLEEP_HOOK.borrow(cs_token).borrow_mut() = Some(hook);
+    })
+}
+
+/// Puts the CPU to sleep with `sleep_instruction`. If a pre-sleep hook is set, it is run with
+/// interrupts masked by PRIMASK and the CPU sleeps only if the hook returns true. PRIMASK is used
+/// even with the `basepri` feature, as an interrupt masked by BASEPRI does not wake the CPU.
+fn sleep(sleep_instruction: fn()) {
+    let hook = critical_section(|cs_token| *PRE_SLEEP_HOOK.borrow(cs_token).borrow());
+    match hook {
+        Some(hook) => cortex_m::interrupt::free(|_| {
+            if hook() {
+                sleep_instruction();
+            }
+        }),
+        None => sleep_instruction(),
+    }
+}
+
+/// Puts the CPU to sleep until an interrupt occurs. An interrupt that arrives after the pre-sleep
+/// hook stays pending and wakes `wfi` immediately, hence no wakeup is lost.
 pub fn wait_for_interrupt() {
-    cortex_m::asm::wfi();
+    sleep(cortex_m::asm::wfi)
+}
+
+/// Sets SEVONPEND, so that an interrupt which becomes pending while the pre-sleep hook masks
+/// interrupts is an event too and wakes `wfe`. Called once when the kernel is initialized.
+pub fn enable_event_on_pending() {
+    const SCB_SCR_SEVONPEND: u32 = 1 << 4;
+    unsafe {
+        let scb = &*cortex_m::peripheral::SCB::ptr();
+        scb.scr.modify(|scr| scr | SCB_SCR_SEVONPEND);
+    }
+}
+
+/// Puts the CPU to sleep until an event or an interrupt occurs, an event signalled while the CPU was
+/// awake wakes `wfe` immediately. As SEVONPEND is set, no wakeup is lost.
+pub fn wait_for_event() {
+    sleep(cortex_m::asm::wfe)
+}
+
+/// The sleep of the idle task when no idle hook is set, `wfi` unless the `idle_wfe` feature is
+/// enabled.
+#[cfg(not(feature = "idle_wfe"))]
+fn idle_sleep() {
+    wait_for_interrupt()
+}
+
+#[cfg(feature = "idle_wfe")]
+fn idle_sleep() {
+    wait_for_event()
+}
+
+/// The sleep depth requested by the idle hook for a single iteration of the idle task.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SleepDepth {
+    /// Do not sleep, the idle task loops immediately.
+    None,
+    /// Sleep until an event or an interrupt occurs (`wfe`).
+    Wfe,
+    /// Set SLEEPDEEP and sleep until an interrupt occurs (stop mode on most parts).
+    DeepSleep,
+}
+
+/// Hook consulted by the idle task on every iteration to decide how deep the CPU should sleep.
+static IDLE_HOOK: KernelCell<Option<fn() -> SleepDepth>> = KernelCell::new(None);
+
+pub fn set_idle_hook(hook: fn() -> SleepDepth) {
+    critical_section(|cs_token| {
+        *IDLE_HOOK.borrow(cs_token).borrow_mut() = Some(hook);
+    })
+}
+
+/// A single iteration of the idle task. Drains the idle log sink, then if an idle hook is registered,
+/// the CPU sleeps as deep as the hook requests, else it sleeps with the instruction selected by the
+/// `idle_wfi` and `idle_wfe` features.
+pub fn idle() {
+    #[cfg(feature = "system_logger")]
+    process_idle_sink();
+
+    let hook = critical_section(|cs_token| *IDLE_HOOK.borrow(cs_token).borrow());
+    match hook {
+        Some(hook) => match hook() {
+            SleepDepth::None => {}
+            SleepDepth::Wfe => wait_for_event(),
+            SleepDepth::DeepSleep => deep_sleep(),
+        },
+        None => idle_sleep(),
+    }
+}
+
+fn deep_sleep() {
+    const SCB_SCR_SLEEPDEEP: u32 = 1 << 2;
+    unsafe {
+        let scb = &*cortex_m::peripheral::SCB::ptr();
+        scb.scr.modify(|scr| scr | SCB_SCR_SLEEPDEEP);
+        cortex_m::asm::dsb();
+        wait_for_interrupt();
+        scb.scr.modify(|scr| scr & !SCB_SCR_SLEEPDEEP);
+    }
 }
 
 /// Returns true if Currently the Kernel is operating in Privileged mode.
@@ -2,6 +2,9 @@
 
 use core::fmt;
 
+#[cfg(feature = "rich_errors")]
+use crate::system::scheduler::TaskId;
+
 /// Error Type for Kernel.
 pub enum KernelError {
     NotFound,
@@ -10,6 +13,32 @@ pub enum KernelError {
     AccessDenied,
     Empty,
     Exists,
+    Cyclic,
+    /// The resource is locked, its ceiling is not above the system ceiling.
+    ResourceBusy,
+    /// The buffer holding the messages of a receiver is full.
+    BufferFull,
+    /// The operation did not complete within the given number of ticks.
+    Timeout,
+    /// The pi_stack overflowed while locking the resource `resource` with ceiling `ceiling`.
+    /// Resources are identified by their address, which is stable as they are statics.
+    #[cfg(feature = "rich_errors")]
+    PiStackFull {
+        resource: usize,
+        ceiling: TaskId,
+        top: usize,
+        system_ceiling: i32,
+    },
+    /// The resource `resource` with ceiling `ceiling` was unlocked while the pi_stack was empty.
+    #[cfg(feature = "rich_errors")]
+    PiStackEmpty {
+        resource: usize,
+        ceiling: TaskId,
+        system_ceiling: i32,
+    },
+    /// The registers of a task were not restored by a context switch.
+    #[cfg(feature = "self_test")]
+    ContextCorrupted,
 }
 
 impl fmt::Debug for KernelError {
@@ -21,6 +50,33 @@ impl fmt::Debug for KernelError {
             KernelError::AccessDenied => write!(f, "AccessDenied"),
             KernelError::Empty => write!(f, "Empty"),
             KernelError::Exists => write!(f, "Exists"),
+            KernelError::Cyclic => write!(f, "Cyclic"),
+            KernelError::ResourceBusy => write!(f, "ResourceBusy"),
+            KernelError::BufferFull => write!(f, "BufferFull"),
+            KernelError::Timeout => write!(f, "Timeout"),
+            #[cfg(feature = "rich_errors")]
+            KernelError::PiStackFull {
+                resource,
+                ceiling,
+                top,
+                system_ceiling,
+            } => write!(
+                f,
+                "PiStackFull {{ resource: {:#x}, ceiling: {}, top: {}, system_ceiling: {} }}",
+                resource, ceiling, top, system_ceiling
+            ),
+            #[cfg(feature = "rich_errors")]
+            KernelError::PiStackEmpty {
+                resource,
+                ceiling,
+                system_ceiling,
+            } => write!(
+                f,
+                "PiStackEmpty {{ resource: {:#x}, ceiling: {}, system_ceiling: {} }}",
+                resource, ceiling, system_ceiling
+            ),
+            #[cfg(feature = "self_test")]
+            KernelError::ContextCorrupted => write!(f, "ContextCorrupted"),
         }
     }
 }
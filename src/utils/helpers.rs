@@ -1,3 +1,12 @@
+/// Returns the index of the most significant set bit of `val`, `None` if `val` is 0. It compiles to
+/// the CLZ instruction.
+pub fn get_msb(val: u32) -> Option<usize> {
+    if val == 0 {
+        return None;
+    }
+    Some(31 - val.leading_zeros() as usize)
+}
+
 /// A helper struct to generate Boolean vector corresponding to an array of TaskIds at compile time.
 pub struct TaskMask<const N: usize> {}
 
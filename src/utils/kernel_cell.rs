@@ -0,0 +1,26 @@
+//! Cell which holds the global state of the Kernel.
+
+use core::cell::RefCell;
+
+use crate::utils::arch::{CriticalSection, Mutex};
+
+/// Every global of the Kernel (the task managers, the pi_stacks, the logger, etc.) is a
+/// `KernelCell`, hence a single synchronization strategy has to be audited. The content is only
+/// accessible within a critical section, which on a single core guarantees exclusive access.
+/// On the dual-core build, the spinlocks of the kernel have to be held in addition.
+pub struct KernelCell<T> {
+    inner: Mutex<RefCell<T>>,
+}
+
+impl<T> KernelCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(value)),
+        }
+    }
+
+    /// Returns the RefCell holding the content, the borrow lasts for the critical section.
+    pub fn borrow<'cs>(&'cs self, cs_token: &'cs CriticalSection) -> &'cs RefCell<T> {
+        self.inner.borrow(cs_token)
+    }
+}
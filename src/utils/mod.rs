@@ -3,6 +3,7 @@
 pub mod arch;
 pub mod errors;
 pub mod helpers;
+pub mod kernel_cell;
 
 #[cfg(feature = "alloc")]
 pub mod heap;